@@ -0,0 +1,52 @@
+use actix_web_queryst::QuerySt;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct Search {
+    term: String,
+    page: u32,
+    per_page: u32,
+    sort: String,
+    order: String,
+    lang: String,
+    region: String,
+    safe: String,
+    from: String,
+    to: String,
+}
+
+fn query(repeat: usize) -> String {
+    let term = "rust%20web%20framework".repeat(repeat);
+    format!(
+        "term={}&page=2&per_page=50&sort=date&order=desc&lang=en&region=eu&safe=off\
+         &from=2020-01-01&to=2021-01-01",
+        term
+    )
+}
+
+fn bench_from_query(c: &mut Criterion) {
+    for &(name, repeat) in &[("small", 1), ("large", 1000)] {
+        let query = query(repeat);
+        // a page that isn't a number fails the deserialization
+        let failing = query.replace("page=2", "page=x");
+        let mut group = c.benchmark_group(name);
+        group.bench_function("from_query", |b| {
+            b.iter(|| QuerySt::<Search>::from_query(black_box(&query)).unwrap())
+        });
+        group.bench_function("from_query_fast", |b| {
+            b.iter(|| QuerySt::<Search>::from_query_fast(black_box(&query)).unwrap())
+        });
+        group.bench_function("from_query failing", |b| {
+            b.iter(|| QuerySt::<Search>::from_query(black_box(&failing)).unwrap_err())
+        });
+        group.bench_function("from_query_fast failing", |b| {
+            b.iter(|| QuerySt::<Search>::from_query_fast(black_box(&failing)).unwrap_err())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_from_query);
+criterion_main!(benches);
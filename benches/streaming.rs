@@ -0,0 +1,104 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web_queryst::QuerySt;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::Deserialize;
+
+/// Allocator keeping track of the peak of allocated bytes
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct Form {
+    name: String,
+    tags: Vec<String>,
+    filter: Filter,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct Filter {
+    status: String,
+    ids: Vec<u64>,
+}
+
+fn query(repeat: usize) -> String {
+    let mut query = format!(
+        "name={}&filter[status]=open",
+        "form-as-query".repeat(repeat)
+    );
+    for i in 0..repeat {
+        query.push_str(&format!("&tags[]=tag-number-{}&filter[ids][]={}", i, i));
+    }
+    query
+}
+
+/// Peak of the bytes allocated while running `f`, on top of what was allocated before
+fn peak_bytes(f: impl FnOnce()) -> usize {
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    f();
+    PEAK.load(Ordering::Relaxed) - base
+}
+
+fn bench_streaming(c: &mut Criterion) {
+    for &(name, repeat) in &[("small", 1), ("large", 50_000)] {
+        let query = query(repeat);
+        // an id that isn't a number fails the deserialization
+        let failing = format!("{}&filter[ids][]=x", query);
+        println!(
+            "{} ({} bytes): peak from_query {} bytes, from_query_streaming {} bytes",
+            name,
+            query.len(),
+            peak_bytes(|| drop(QuerySt::<Form>::from_query(&query).unwrap())),
+            peak_bytes(|| drop(QuerySt::<Form>::from_query_streaming(&query).unwrap())),
+        );
+        println!(
+            "{} failing: peak from_query {} bytes, from_query_streaming {} bytes",
+            name,
+            peak_bytes(|| drop(QuerySt::<Form>::from_query(&failing).unwrap_err())),
+            peak_bytes(|| drop(QuerySt::<Form>::from_query_streaming(&failing).unwrap_err())),
+        );
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("from_query", |b| {
+            b.iter(|| QuerySt::<Form>::from_query(black_box(&query)).unwrap())
+        });
+        group.bench_function("from_query_streaming", |b| {
+            b.iter(|| QuerySt::<Form>::from_query_streaming(black_box(&query)).unwrap())
+        });
+        group.bench_function("from_query failing", |b| {
+            b.iter(|| QuerySt::<Form>::from_query(black_box(&failing)).unwrap_err())
+        });
+        group.bench_function("from_query_streaming failing", |b| {
+            b.iter(|| QuerySt::<Form>::from_query_streaming(black_box(&failing)).unwrap_err())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_streaming);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! Feed arbitrary bytes as a query string to `QuerySt::from_query`, which must return an
+//! error rather than panic on any of them.
+//!
+//! Run with `cargo +nightly fuzz run from_query` from the repository root.
+
+#![no_main]
+
+use actix_web_queryst::QuerySt;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let query = String::from_utf8_lossy(data);
+    let _ = QuerySt::<serde_json::Value>::from_query(&query);
+});
@@ -0,0 +1,110 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, web::HttpRequest, FromRequest};
+use serde::de;
+
+use crate::{parse_req, Extraction, QuerySt, QueryStConfig, QueryStPayloadError};
+
+/// Extract information from the request's query using `queryst`, rejecting query strings
+/// longer than `MAX` bytes.
+///
+/// Works like [**QuerySt**](struct.QuerySt.html) with a `max_length` of `MAX`, without
+/// registering a [**QueryStConfig**](struct.QueryStConfig.html) for it. A longer query is
+/// rejected with an `Overflow` error, which responds with `413 Payload Too Large` unless the
+/// config's error handler says otherwise. `max_length` of the config still applies as well.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::BoundedQuerySt;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Search {
+///    q: String,
+/// }
+///
+/// async fn index(search: BoundedQuerySt<Search, 256>) -> String {
+///     format!("Searching for {}", search.q)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/search").route(web::get().to(index)));
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct BoundedQuerySt<T, const MAX: usize>(pub T);
+
+impl_wrapper!(BoundedQuerySt<T, const MAX: usize>, T);
+
+impl<T, const MAX: usize> FromRequest for BoundedQuerySt<T, MAX>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, actix_web::Error>>;
+    type Config = QueryStConfig;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = QueryStConfig::from_req(req);
+        let length = req.query_string().len();
+        let mut extraction = Extraction::start(req, "BoundedQuerySt", length);
+        let r = extraction.run(|extraction| {
+            let r = if length > MAX {
+                Err(QueryStPayloadError::Overflow {
+                    length,
+                    limit: MAX,
+                    query: None,
+                })
+            } else {
+                parse_req(req, config)
+            };
+            r.and_then(|value| {
+                extraction.parsed(&value);
+                QuerySt::from_value_with(value, config)
+            })
+        });
+        extraction.finish(req, &r);
+        ready(
+            r.map(|QuerySt(inner)| BoundedQuerySt(inner))
+                .map_err(|e| config.handle_error(e, req)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Id {
+        id: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_bounded_request_extract() {
+        let req = TestRequest::with_uri("/?id=test").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = BoundedQuerySt::<Id, 8>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.id, "test");
+
+        let req = TestRequest::with_uri("/?id=testing").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let err = BoundedQuerySt::<Id, 8>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
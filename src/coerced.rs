@@ -0,0 +1,286 @@
+//! Deserialization of a normalized `serde_json::Value`, coercing the strings that look like
+//! numbers into whatever the target type asks for.
+
+use serde::de::{
+    self, value::MapAccessDeserializer, value::MapDeserializer, value::SeqDeserializer,
+    IntoDeserializer, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use serde_json::Value;
+
+use crate::normalize::parse_number;
+
+/// Deserialize `T` from `value`, number-like strings giving numbers to numeric fields and
+/// the original text to string fields.
+///
+/// Types that don't say what they expect (ex: `serde_json::Value`) get numbers. Those that
+/// serde buffers first (flattened fields and untagged enums) can't tell either, so when
+/// giving them numbers fails they are deserialized again with the strings, keeping the
+/// first error if that fails too.
+pub(crate) fn deserialize<'de, T>(
+    value: &'de Value,
+) -> Result<T, serde_path_to_error::Error<serde_json::Error>>
+where
+    T: de::Deserialize<'de>,
+{
+    serde_path_to_error::deserialize(Coerced::new(value, true))
+        .or_else(|e| serde_path_to_error::deserialize(Coerced::new(value, false)).map_err(|_| e))
+}
+
+/// Deserializer for a normalized value, see `deserialize`
+#[derive(Clone, Copy)]
+pub(crate) struct Coerced<'de> {
+    value: &'de Value,
+    /// Whether number-like strings are numbers for `deserialize_any`
+    numbers: bool,
+}
+
+impl<'de> Coerced<'de> {
+    pub(crate) fn new(value: &'de Value, numbers: bool) -> Self {
+        Coerced { value, numbers }
+    }
+
+    fn with(self, value: &'de Value) -> Self {
+        Coerced { value, ..self }
+    }
+
+    fn deserialize_number<V>(self, visitor: V) -> Result<V::Value, serde_json::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.as_str().and_then(parse_number) {
+            Some(number) => de::Deserializer::deserialize_any(number, visitor),
+            None => de::Deserializer::deserialize_any(self, visitor),
+        }
+    }
+
+    fn map(
+        self,
+        map: &'de serde_json::Map<String, Value>,
+    ) -> MapDeserializer<'de, impl Iterator<Item = (&'de str, Coerced<'de>)>, serde_json::Error>
+    {
+        MapDeserializer::new(
+            map.iter()
+                .map(move |(key, value)| (key.as_str(), self.with(value))),
+        )
+    }
+}
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for Coerced<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_numbers {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.deserialize_number(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Coerced<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::String(s) => match parse_number(s).filter(|_| self.numbers) {
+                Some(number) => de::Deserializer::deserialize_any(number, visitor),
+                None => visitor.visit_borrowed_str(s),
+            },
+            Value::Array(items) => {
+                let mut seq = SeqDeserializer::new(items.iter().map(|item| self.with(item)));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Object(map) => {
+                let mut map = self.map(map);
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::String(s) => visitor.visit_borrowed_str(s),
+            value => value.deserialize_str(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Object(map) if map.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(self.map(map)))
+            }
+            value => value.deserialize_enum(name, variants, visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    deserialize_numbers! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+    }
+
+    forward_to_deserialize_any! {
+        bool bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Mixed {
+        id: u64,
+        name: String,
+        ratio: Option<f64>,
+        tags: Vec<String>,
+        counts: Vec<i32>,
+    }
+
+    #[test]
+    fn test_coerced_by_target() {
+        let value = json!({
+            "id": "64",
+            "name": "123",
+            "ratio": "1e3",
+            "tags": ["1.5", "x"],
+            "counts": ["-1", "2"],
+        });
+        let s: Mixed = deserialize(&value).unwrap();
+        assert_eq!(
+            s,
+            Mixed {
+                id: 64,
+                name: "123".to_owned(),
+                ratio: Some(1000.0),
+                tags: vec!["1.5".to_owned(), "x".to_owned()],
+                counts: vec![-1, 2],
+            }
+        );
+
+        let value = json!({"a": "1", "b": ["2", "x"], "c": {"d": "007", "e": "-1.5"}});
+        let coerced: Value = deserialize(&value).unwrap();
+        assert_eq!(
+            coerced,
+            json!({"a": 1, "b": [2, "x"], "c": {"d": "007", "e": -1.5}})
+        );
+    }
+
+    #[test]
+    fn test_coerced_buffered() {
+        #[derive(Deserialize, Debug)]
+        struct Page {
+            page: u32,
+            #[serde(flatten)]
+            rest: HashMap<String, String>,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(untagged)]
+        enum Either {
+            Number { n: u8 },
+            Text { n: String },
+        }
+
+        let value = json!({"page": "2", "zip": "12345", "name": "x"});
+        let s: Page = deserialize(&value).unwrap();
+        assert_eq!(s.page, 2);
+        assert_eq!(s.rest["zip"], "12345");
+
+        let value = json!({"n": "5"});
+        assert_eq!(
+            deserialize::<Either>(&value).unwrap(),
+            Either::Number { n: 5 }
+        );
+        let value = json!({"n": "500"});
+        assert_eq!(
+            deserialize::<Either>(&value).unwrap(),
+            Either::Text {
+                n: "500".to_owned()
+            }
+        );
+    }
+}
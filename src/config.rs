@@ -0,0 +1,1148 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::web::HttpRequest;
+use actix_web::{HttpResponse, ResponseError};
+use derive_more::Display;
+
+use crate::{QueryStErrorKind, QueryStPayloadError};
+
+/// QuerySt extractor configuration
+///
+/// Options are applied in a fixed order, whatever order they are set in: the limits on the raw
+/// query string first, then `allow_semicolons`, `key_notation`, `duplicate_policy`,
+/// `plus_as_space`, `charset`, `case_insensitive_keys`, parsing, the limits on the parsed value
+/// (`max_nodes`, `max_depth`, `max_array_len`, `max_value_bytes`), `bare_flags_as_true`,
+/// `null_sentinel`, `alias`, `numeric_keys_to_array`, `split_on`, the limits on the parsed
+/// value again if either of those two is enabled, `trim_values`, `dedup_arrays`,
+/// `empty_as_none`, `null_literal`, `normalize_case_for`, `coerce_bools`, `radix_literals` and
+/// finally the `transform` closures. See also [`QueryStConfig::builder`](#method.builder).
+/// The limits on the parsed value share a single walk, and so do the value coercions from
+/// `empty_as_none` to `radix_literals`. Numeric coercion comes last, while deserializing,
+/// since it depends on whether the field expects a number or a string.
+///
+/// A config registered on a resource (or scope) with `app_data` takes precedence over one
+/// registered on the whole `App`, which takes precedence over the default config. Configs
+/// are not merged, the closest one is used as a whole.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{error, web, App, FromRequest, HttpResponse};
+/// use actix_web_queryst::{QuerySt, QueryStConfig};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's query
+/// async fn index(info: QuerySt<Info>) -> String {
+///     format!("Welcome {}!", info.username)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/index.html").app_data(
+///             // change QuerySt extractor configuration
+///             QueryStConfig::default()
+///                 .error_handler(|err, req| {  // <- create custom error response
+///                     error::InternalError::from_response(
+///                         err, HttpResponse::Conflict().finish()).into()
+///                 })
+///             )
+///             .route(web::post().to(index))
+///     );
+/// }
+/// ```
+#[derive(Clone)]
+pub struct QueryStConfig {
+    pub(crate) ehandler: Option<ErrorHandler>,
+    pub(crate) coerce_bools: bool,
+    pub(crate) coerce_bool_digits: bool,
+    pub(crate) bool_tokens: Option<BoolTokens>,
+    pub(crate) empty_as_none: bool,
+    pub(crate) max_length: Option<usize>,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_params: Option<usize>,
+    pub(crate) split_on: Option<char>,
+    pub(crate) case_insensitive_keys: bool,
+    pub(crate) deny_unknown: bool,
+    pub(crate) strict_percent_encoding: bool,
+    pub(crate) lossy_utf8: bool,
+    pub(crate) null_literal: bool,
+    pub(crate) null_sentinel: Option<String>,
+    pub(crate) numeric_keys_to_array: bool,
+    pub(crate) aliases: Option<Vec<(String, String)>>,
+    pub(crate) case_modes: Option<HashMap<String, CaseMode>>,
+    pub(crate) duplicate_policy: Option<DuplicatePolicy>,
+    pub(crate) key_notation: Option<Notation>,
+    pub(crate) plus_as_space: bool,
+    pub(crate) max_array_len: Option<usize>,
+    pub(crate) max_value_bytes: Option<usize>,
+    pub(crate) trim_values: bool,
+    pub(crate) radix_literals: bool,
+    pub(crate) dedup_arrays: bool,
+    pub(crate) bare_flags_as_true: bool,
+    pub(crate) allow_semicolons: bool,
+    pub(crate) required_keys: Option<Vec<String>>,
+    pub(crate) allowed_keys: Option<HashSet<String>>,
+    #[cfg(feature = "encoding_rs")]
+    pub(crate) charset: Option<&'static encoding_rs::Encoding>,
+    pub(crate) transforms: Option<Vec<Transform>>,
+    pub(crate) statuses: Option<HashMap<QueryStErrorKind, StatusCode>>,
+    pub(crate) json_errors: bool,
+}
+
+type ErrorHandler =
+    Arc<dyn Fn(QueryStPayloadError, &HttpRequest) -> actix_web::Error + Send + Sync>;
+
+type Transform = Arc<dyn Fn(&mut serde_json::Value) + Send + Sync>;
+
+/// The strings `coerce_bools` converts, set with `bool_tokens`
+#[derive(Clone)]
+pub(crate) struct BoolTokens {
+    pub(crate) truthy: Vec<String>,
+    pub(crate) falsy: Vec<String>,
+}
+
+/// Truthy tokens of HTML forms and checkboxes, for
+/// [`QueryStConfig::bool_tokens`](struct.QueryStConfig.html#method.bool_tokens)
+pub const FORM_TRUTHY_TOKENS: &[&str] = &["true", "on", "yes"];
+
+/// Falsy tokens of HTML forms and checkboxes, for
+/// [`QueryStConfig::bool_tokens`](struct.QueryStConfig.html#method.bool_tokens)
+pub const FORM_FALSY_TOKENS: &[&str] = &["false", "off", "no"];
+
+impl QueryStConfig {
+    pub(crate) fn from_req(req: &HttpRequest) -> &Self {
+        req.app_data::<Self>().unwrap_or(&DEFAULT_CONFIG)
+    }
+
+    pub(crate) fn handle_error(
+        &self,
+        e: QueryStPayloadError,
+        req: &HttpRequest,
+    ) -> actix_web::Error {
+        self.handle_error_with(e, req, req.query_string())
+    }
+
+    /// Same as `handle_error` for an error about `query` rather than the request's query
+    pub(crate) fn handle_error_with(
+        &self,
+        e: QueryStPayloadError,
+        req: &HttpRequest,
+        query: &str,
+    ) -> actix_web::Error {
+        let e = e.with_query(query);
+        if let Some(error_handler) = &self.ehandler {
+            return (error_handler)(e, req);
+        }
+        let status = self
+            .statuses
+            .as_ref()
+            .and_then(|s| s.get(&e.kind()))
+            .copied();
+        if self.json_errors {
+            let body = serde_json::json!({"error": e.to_string(), "kind": e.kind().as_str()});
+            let response =
+                HttpResponse::build(status.unwrap_or_else(|| e.status_code())).json(body);
+            InternalError::from_response(e, response).into()
+        } else if let Some(status) = status {
+            InternalError::new(e, status).into()
+        } else {
+            e.into()
+        }
+    }
+
+    /// Set custom error handler
+    ///
+    /// The errors it receives carry the query that failed, see
+    /// [`QueryStPayloadError::query`](enum.QueryStPayloadError.html#method.query).
+    pub fn error_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn(QueryStPayloadError, &HttpRequest) -> actix_web::Error + Send + Sync + 'static,
+    {
+        self.ehandler = Some(Arc::new(f));
+        self
+    }
+
+    /// Respond with `status` to errors of the given kind, instead of the status of the
+    /// error itself
+    ///
+    /// The response body stays the error message. Ignored when an
+    /// [`error_handler`](#method.error_handler) is set, which gets the error as is.
+    pub fn status_for(mut self, kind: QueryStErrorKind, status: StatusCode) -> Self {
+        self.statuses
+            .get_or_insert_with(HashMap::new)
+            .insert(kind, status);
+        self
+    }
+
+    /// Respond to errors with a JSON body, ex: `{"error": "QuerySt ...", "kind": "type_mismatch"}`
+    ///
+    /// `kind` is the [`as_str`](enum.QueryStErrorKind.html#method.as_str) of the error's
+    /// kind. The status is the one of the error or of [`status_for`](#method.status_for).
+    /// Ignored when an [`error_handler`](#method.error_handler) is set. Disabled by default,
+    /// in which case the body is the error message as plain text.
+    pub fn json_errors(mut self, enabled: bool) -> Self {
+        self.json_errors = enabled;
+        self
+    }
+
+    /// Register a closure rewriting the parsed value before deserialization
+    ///
+    /// Transformers run after every built-in option, in the order they were registered, and
+    /// can be used for coercions this config doesn't provide. Values that look like numbers
+    /// are still strings at this point.
+    pub fn transform<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut serde_json::Value) + Send + Sync + 'static,
+    {
+        self.transforms
+            .get_or_insert_with(Vec::new)
+            .push(Arc::new(f));
+        self
+    }
+
+    /// Convert `"true"` and `"false"` values into booleans before deserialization
+    ///
+    /// Values that don't match are left untouched, see [`bool_tokens`](#method.bool_tokens)
+    /// to convert other values. Disabled by default.
+    pub fn coerce_bools(mut self, enabled: bool) -> Self {
+        self.coerce_bools = enabled;
+        self
+    }
+
+    /// Also convert `"1"` and `"0"` values into booleans when `coerce_bools` is enabled
+    ///
+    /// These values are converted before numeric coercion, so they no longer reach numeric
+    /// fields. Disabled by default.
+    pub fn coerce_bool_digits(mut self, enabled: bool) -> Self {
+        self.coerce_bool_digits = enabled;
+        self
+    }
+
+    /// Make `coerce_bools` convert the values in `truthy` and `falsy` instead of `"true"` and
+    /// `"false"`, ignoring ASCII case
+    ///
+    /// [`FORM_TRUTHY_TOKENS`](constant.FORM_TRUTHY_TOKENS.html) and
+    /// [`FORM_FALSY_TOKENS`](constant.FORM_FALSY_TOKENS.html) add `on`/`off` (as sent for
+    /// checkboxes) and `yes`/`no`. `coerce_bool_digits` still adds `"1"` and `"0"`.
+    ///
+    /// ```rust
+    /// use actix_web_queryst::{QuerySt, QueryStConfig, FORM_FALSY_TOKENS, FORM_TRUTHY_TOKENS};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Signup {
+    ///     subscribe: bool,
+    /// }
+    ///
+    /// let config = QueryStConfig::default()
+    ///     .coerce_bools(true)
+    ///     .bool_tokens(FORM_TRUTHY_TOKENS, FORM_FALSY_TOKENS);
+    /// let signup = QuerySt::<Signup>::from_query_with("subscribe=on", &config).unwrap();
+    /// assert!(signup.subscribe);
+    /// ```
+    pub fn bool_tokens(mut self, truthy: &[&str], falsy: &[&str]) -> Self {
+        let tokens = |tokens: &[&str]| tokens.iter().map(|t| t.to_ascii_lowercase()).collect();
+        self.bool_tokens = Some(BoolTokens {
+            truthy: tokens(truthy),
+            falsy: tokens(falsy),
+        });
+        self
+    }
+
+    /// Treat empty values (ex: `?name=`) as missing, so `Option` fields resolve to `None`
+    ///
+    /// Disabled by default, in which case an `Option<String>` field receives `Some("")`.
+    pub fn empty_as_none(mut self, enabled: bool) -> Self {
+        self.empty_as_none = enabled;
+        self
+    }
+
+    /// Reject query strings longer than `limit` bytes before parsing them
+    ///
+    /// Unlimited by default.
+    pub fn max_length(mut self, limit: usize) -> Self {
+        self.max_length = Some(limit);
+        self
+    }
+
+    /// Reject queries whose parsed value has more than `limit` nodes, counting arrays and
+    /// objects as well as the values they hold (ex: `a=1&b[]=2` has four)
+    ///
+    /// All the limits on the parsed value are checked in a single walk, which stops as soon as
+    /// the budget is exhausted. The walk is repeated after `split_on` and
+    /// `numeric_keys_to_array`, which can add nodes. Unlimited by default.
+    pub fn max_nodes(mut self, limit: usize) -> Self {
+        self.max_nodes = Some(limit);
+        self
+    }
+
+    /// Reject queries whose parsed arrays and objects are nested more than `limit` levels deep
+    ///
+    /// The top-level object counts as the first level, so `a=1` has a depth of one and
+    /// `a[b][]=1` a depth of three. Unlimited by default.
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.max_depth = Some(limit);
+        self
+    }
+
+    /// Reject queries with a parsed array of more than `limit` elements, nested ones included
+    ///
+    /// Empty elements pushed with `items[]=` are dropped before the check and not counted,
+    /// those of repeated keys (ex: `t=a&t=`) are. The lists made by `split_on` and
+    /// `numeric_keys_to_array` are checked too. Unlimited by default.
+    pub fn max_array_len(mut self, limit: usize) -> Self {
+        self.max_array_len = Some(limit);
+        self
+    }
+
+    /// Reject queries whose parsed value would take more than `limit` bytes once serialized
+    /// as JSON, since the bracket syntax can expand a short query into a large structure
+    ///
+    /// The size is estimated while walking the value rather than by serializing it.
+    /// Unlimited by default.
+    pub fn max_value_bytes(mut self, limit: usize) -> Self {
+        self.max_value_bytes = Some(limit);
+        self
+    }
+
+    /// Reject query strings with more than `limit` `&`-separated pairs before parsing them
+    ///
+    /// Empty pairs (ex: `a=1&&b=2`) are not counted. Unlimited by default.
+    pub fn max_params(mut self, limit: usize) -> Self {
+        self.max_params = Some(limit);
+        self
+    }
+
+    /// Split values containing `delimiter` into lists, so `ids=1,2,3` works like
+    /// `ids[]=1&ids[]=2&ids[]=3`
+    ///
+    /// Values without the delimiter are left as they are, so a list field with a single
+    /// element still needs the bracket syntax (`ids[]=1`). Empty segments (ex: `ids=1,,2`)
+    /// are preserved as empty strings unless `empty_as_none` is enabled as well, in which
+    /// case they become `null`, a combination the
+    /// [**QueryStConfigBuilder**](struct.QueryStConfigBuilder.html) rejects. Disabled by
+    /// default.
+    pub fn split_on(mut self, delimiter: char) -> Self {
+        self.split_on = Some(delimiter);
+        self
+    }
+
+    /// Trim leading and trailing whitespace from values, so `name=%20john%20` gives `"john"`
+    ///
+    /// Applies to decoded values, nested ones included, and to the segments produced by
+    /// `split_on`. A value of whitespace only becomes empty, and `null` if `empty_as_none` is
+    /// enabled as well. Disabled by default, since whitespace can be significant.
+    pub fn trim_values(mut self, enabled: bool) -> Self {
+        self.trim_values = enabled;
+        self
+    }
+
+    /// Convert hexadecimal, octal and binary literals (ex: `0xFF`, `0o17`, `0b1010`) into
+    /// numbers
+    ///
+    /// Prefixes are lowercase only. Values that don't fit in an unsigned 64-bit integer are
+    /// left as strings, so a numeric field reports them as a type error. Disabled by default,
+    /// since string fields can hold such values too.
+    pub fn radix_literals(mut self, enabled: bool) -> Self {
+        self.radix_literals = enabled;
+        self
+    }
+
+    /// Remove repeated values from lists, keeping the first occurrence of each
+    ///
+    /// `tag[]=a&tag[]=b&tag[]=a` gives `["a", "b"]`. Values are compared as received, before
+    /// numeric coercion, so `1` and `1.0` both stay. Lists holding lists or maps are left
+    /// untouched. Disabled by default.
+    pub fn dedup_arrays(mut self, enabled: bool) -> Self {
+        self.dedup_arrays = enabled;
+        self
+    }
+
+    /// Decode percent-encoded bytes of the query in `encoding` instead of UTF-8, for legacy
+    /// clients (ex: `encoding_rs::WINDOWS_1252` for Latin-1)
+    ///
+    /// The query is transcoded before parsing. By default invalid UTF-8 sequences are
+    /// replaced with `U+FFFD`.
+    #[cfg(feature = "encoding_rs")]
+    pub fn charset(mut self, encoding: &'static encoding_rs::Encoding) -> Self {
+        self.charset = Some(encoding);
+        self
+    }
+
+    /// Treat keys without a value (ex: `?verbose`) as `true`, so they fill `bool` fields
+    ///
+    /// Only keys without `=` are affected, `?verbose=` still gives an empty string. Disabled
+    /// by default, in which case such keys are `null` and only fill `Option` fields.
+    pub fn bare_flags_as_true(mut self, enabled: bool) -> Self {
+        self.bare_flags_as_true = enabled;
+        self
+    }
+
+    /// Lowercase every key of the parsed query, including nested ones (ex: `User[Name]=x`)
+    ///
+    /// Fields of the target type need lowercase names for this to be useful. Keys are
+    /// lowercased before parsing, so nested objects spelled differently are merged. When
+    /// several spellings of a key collide, the one given last in the query wins, so
+    /// `id=1&ID=2` gives `2` and `ID=1&id=2` gives `2` as well, while repeating the same
+    /// spelling still gives a list. Disabled by default.
+    pub fn case_insensitive_keys(mut self, enabled: bool) -> Self {
+        self.case_insensitive_keys = enabled;
+        self
+    }
+
+    /// Reject queries with top-level keys the target struct doesn't declare
+    ///
+    /// Works like serde's `deny_unknown_fields`, but reports the offending key in a
+    /// dedicated `UnknownKey` error. Only applies when the target deserializes from a plain
+    /// struct; maps, `serde_json::Value` and structs with `#[serde(flatten)]` fields are not
+    /// checked, derive them with `deny_unknown_fields` instead. Disabled by default.
+    pub fn deny_unknown(mut self, enabled: bool) -> Self {
+        self.deny_unknown = enabled;
+        self
+    }
+
+    /// Reject queries missing any of these top-level keys, whatever the target type makes
+    /// optional
+    ///
+    /// The absent keys are listed in a `MissingRequired` error. Keys are looked up after
+    /// `case_insensitive_keys` and `alias`, a key without value counts as present.
+    pub fn require_keys(mut self, keys: &[&str]) -> Self {
+        self.required_keys = Some(keys.iter().map(|key| (*key).to_owned()).collect());
+        self
+    }
+
+    /// Reject queries with a top-level key not in `keys`, whatever the target type declares
+    ///
+    /// The first key outside the list is reported in a `ForbiddenKey` error. Unlike
+    /// `deny_unknown` this works for any target type, maps and flattened structs included.
+    /// Keys are looked up after `case_insensitive_keys` and `alias`.
+    pub fn allowed_keys(mut self, keys: &[&str]) -> Self {
+        self.allowed_keys = Some(keys.iter().map(|key| (*key).to_owned()).collect());
+        self
+    }
+
+    /// Reject query strings with malformed percent escapes (ex: `%ZZ`) before parsing them
+    ///
+    /// By default such sequences are passed through as they are.
+    pub fn strict_percent_encoding(mut self, enabled: bool) -> Self {
+        self.strict_percent_encoding = enabled;
+        self
+    }
+
+    /// Replace invalid UTF-8 with `U+FFFD`, in percent-encoded sequences (ex: `%FF`) and in
+    /// form bodies
+    ///
+    /// Enabled by default. When disabled, such input is rejected with an `InvalidEncoding`
+    /// error instead. Sequences are not checked when a `charset` is set, since they aren't
+    /// UTF-8 then.
+    pub fn lossy_utf8(mut self, enabled: bool) -> Self {
+        self.lossy_utf8 = enabled;
+        self
+    }
+
+    /// Convert `"null"` values into `null`, so `Option` fields resolve to `None`
+    ///
+    /// Only the exact lowercase string matches. This runs before bool and number coercion.
+    /// Disabled by default, since `"null"` can be a legitimate value.
+    pub fn null_literal(mut self, enabled: bool) -> Self {
+        self.null_literal = enabled;
+        self
+    }
+
+    /// Treat a top-level key ending with `suffix` as an explicit `null` for the key without
+    /// it, ex: `field__null=1` with the suffix `__null`
+    ///
+    /// This tells an absent key apart from an explicit `null`, ex: for `Option<Option<T>>`
+    /// fields in PATCH-like handlers. The value of the sentinel is ignored, and the sentinel
+    /// wins over a value given for the key itself. Keys are matched after
+    /// `case_insensitive_keys`, so the suffix must be lowercase when it is enabled, and
+    /// before `alias`, which applies to the resulting key.
+    pub fn null_sentinel(mut self, suffix: &str) -> Self {
+        self.null_sentinel = Some(suffix.to_owned());
+        self
+    }
+
+    /// Turn explicitly indexed lists (ex: `items[0]=a&items[2]=c`) into arrays
+    ///
+    /// `queryst` only produces an array when the indices are dense and in order, otherwise
+    /// the list ends up as an object with `"0"`, `"2"` keys. With this option such objects
+    /// become arrays ordered by index, and gaps are compacted instead of filled with `null`,
+    /// so the example above gives `["a", "c"]`. Objects mixing indices and other keys are
+    /// left untouched. Disabled by default.
+    pub fn numeric_keys_to_array(mut self, enabled: bool) -> Self {
+        self.numeric_keys_to_array = enabled;
+        self
+    }
+
+    /// Accept the top-level key `old` as if it were `new`, ex: for renamed parameters
+    ///
+    /// Can be called several times to register several aliases, registering the same `old`
+    /// key twice keeps the last target. If the query has both keys, the value of `new` is
+    /// used and the aliased one is dropped. If it has several aliases of the same `new` key,
+    /// the alias registered first is used. Aliases apply after `case_insensitive_keys`, so
+    /// they must be lowercase when it is enabled.
+    pub fn alias(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        insert_alias(
+            self.aliases.get_or_insert_with(Vec::new),
+            old.into(),
+            new.into(),
+        );
+        self
+    }
+
+    /// Rewrite the string values of the top-level keys `keys` in `mode`, ex: to match the
+    /// variants of an enum whatever case clients send them in
+    ///
+    /// Strings nested under those keys are rewritten too. Can be called several times,
+    /// registering the same key twice keeps the last mode. Keys are looked up after
+    /// `case_insensitive_keys` and `alias`.
+    pub fn normalize_case_for(mut self, keys: &[&str], mode: CaseMode) -> Self {
+        let case_modes = self.case_modes.get_or_insert_with(HashMap::new);
+        for key in keys {
+            case_modes.insert((*key).to_owned(), mode);
+        }
+        self
+    }
+
+    /// Choose what happens to keys given more than once (ex: `id=a&id=b`)
+    ///
+    /// By default repeated keys are collected into a list, which fails to deserialize into a
+    /// single value. Keys ending with `[]` are always collected. Keys are compared once
+    /// percent-decoded, so `a[b]` and `a%5Bb%5D` are the same key.
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = Some(policy);
+        self
+    }
+
+    /// Choose how nested keys are written, ex: `Notation::Dot` to accept `user.name=x` as
+    /// `user[name]=x`
+    ///
+    /// Brackets are always accepted, so both notations can be mixed. Only the keys are
+    /// rewritten, dots in values are kept, and so are dots inside brackets, dots that don't
+    /// separate two non-empty segments (ex: `a.=1`) and encoded ones (`%2E`), which is how
+    /// a key can keep a literal dot.
+    pub fn key_notation(mut self, notation: Notation) -> Self {
+        self.key_notation = Some(notation);
+        self
+    }
+
+    /// Accept `;` as a pair separator alongside `&` (ex: `a=1;b=2`), like older clients send
+    ///
+    /// Disabled by default since `;` is a valid value character, in which case `a=1;b=2`
+    /// gives `a` the value `1;b=2`. An encoded `%3B` always stays part of the value.
+    /// `max_params` counts the pairs separated by either when enabled.
+    pub fn allow_semicolons(mut self, enabled: bool) -> Self {
+        self.allow_semicolons = enabled;
+        self
+    }
+
+    /// Decode `+` as a space (ex: `name=John+Doe` gives `"John Doe"`), like form encoding does
+    ///
+    /// Enabled by default since `queryst` always does it. When disabled, `+` is kept as a
+    /// literal plus sign and only `%20` decodes to a space.
+    pub fn plus_as_space(mut self, enabled: bool) -> Self {
+        self.plus_as_space = enabled;
+        self
+    }
+
+    /// Take the settings of `other` that this config leaves at their default, ex: to
+    /// specialize a base config per resource
+    ///
+    /// Settings of `self` win: a limit, error handler or delimiter set on both is kept from
+    /// `self`, and a flag is enabled (or `plus_as_space` and `lossy_utf8` disabled) if either
+    /// config does so. Aliases, `normalize_case_for` keys and `status_for` mappings are merged
+    /// with the ones of `self` taking precedence, and the transformers of `other` run before
+    /// the ones of `self`.
+    pub fn extend(mut self, other: &QueryStConfig) -> Self {
+        let QueryStConfig {
+            ehandler,
+            coerce_bools,
+            coerce_bool_digits,
+            bool_tokens,
+            empty_as_none,
+            max_length,
+            max_nodes,
+            max_depth,
+            max_params,
+            split_on,
+            case_insensitive_keys,
+            deny_unknown,
+            strict_percent_encoding,
+            lossy_utf8,
+            null_literal,
+            null_sentinel,
+            numeric_keys_to_array,
+            aliases,
+            case_modes,
+            duplicate_policy,
+            key_notation,
+            plus_as_space,
+            max_array_len,
+            max_value_bytes,
+            trim_values,
+            radix_literals,
+            dedup_arrays,
+            bare_flags_as_true,
+            allow_semicolons,
+            required_keys,
+            allowed_keys,
+            #[cfg(feature = "encoding_rs")]
+            charset,
+            transforms,
+            statuses,
+            json_errors,
+        } = other.clone();
+
+        self.ehandler = self.ehandler.or(ehandler);
+        self.coerce_bools |= coerce_bools;
+        self.coerce_bool_digits |= coerce_bool_digits;
+        self.bool_tokens = self.bool_tokens.or(bool_tokens);
+        self.empty_as_none |= empty_as_none;
+        self.max_length = self.max_length.or(max_length);
+        self.max_nodes = self.max_nodes.or(max_nodes);
+        self.max_depth = self.max_depth.or(max_depth);
+        self.max_params = self.max_params.or(max_params);
+        self.split_on = self.split_on.or(split_on);
+        self.case_insensitive_keys |= case_insensitive_keys;
+        self.deny_unknown |= deny_unknown;
+        self.strict_percent_encoding |= strict_percent_encoding;
+        self.lossy_utf8 &= lossy_utf8;
+        self.null_literal |= null_literal;
+        self.null_sentinel = self.null_sentinel.or(null_sentinel);
+        self.numeric_keys_to_array |= numeric_keys_to_array;
+        self.aliases = match (aliases, self.aliases) {
+            (Some(mut base), Some(own)) => {
+                for (old, new) in own {
+                    insert_alias(&mut base, old, new);
+                }
+                Some(base)
+            }
+            (base, own) => own.or(base),
+        };
+        self.case_modes = merge_maps(case_modes, self.case_modes);
+        self.duplicate_policy = self.duplicate_policy.or(duplicate_policy);
+        self.key_notation = self.key_notation.or(key_notation);
+        self.plus_as_space &= plus_as_space;
+        self.max_array_len = self.max_array_len.or(max_array_len);
+        self.max_value_bytes = self.max_value_bytes.or(max_value_bytes);
+        self.trim_values |= trim_values;
+        self.radix_literals |= radix_literals;
+        self.dedup_arrays |= dedup_arrays;
+        self.bare_flags_as_true |= bare_flags_as_true;
+        self.allow_semicolons |= allow_semicolons;
+        self.required_keys = self.required_keys.or(required_keys);
+        self.allowed_keys = self.allowed_keys.or(allowed_keys);
+        #[cfg(feature = "encoding_rs")]
+        {
+            self.charset = self.charset.or(charset);
+        }
+        self.transforms = match (transforms, self.transforms) {
+            (Some(mut base), Some(own)) => {
+                base.extend(own);
+                Some(base)
+            }
+            (base, own) => own.or(base),
+        };
+        self.statuses = merge_maps(statuses, self.statuses);
+        self.json_errors |= json_errors;
+        self
+    }
+}
+
+/// Register the alias `old` of `new`, replacing the target of `old` in place if it has one
+fn insert_alias(aliases: &mut Vec<(String, String)>, old: String, new: String) {
+    match aliases.iter_mut().find(|(key, _)| *key == old) {
+        Some((_, target)) => *target = new,
+        None => aliases.push((old, new)),
+    }
+}
+
+/// Merge two optional maps, the entries of `own` replacing the ones of `base`
+fn merge_maps<K, V>(
+    base: Option<HashMap<K, V>>,
+    own: Option<HashMap<K, V>>,
+) -> Option<HashMap<K, V>>
+where
+    K: std::hash::Hash + Eq,
+{
+    match (base, own) {
+        (Some(mut base), Some(own)) => {
+            base.extend(own);
+            Some(base)
+        }
+        (base, own) => own.or(base),
+    }
+}
+
+pub(crate) const DEFAULT_CONFIG: QueryStConfig = QueryStConfig {
+    ehandler: None,
+    coerce_bools: false,
+    coerce_bool_digits: false,
+    bool_tokens: None,
+    empty_as_none: false,
+    max_length: None,
+    max_nodes: None,
+    max_depth: None,
+    max_params: None,
+    split_on: None,
+    case_insensitive_keys: false,
+    deny_unknown: false,
+    strict_percent_encoding: false,
+    lossy_utf8: true,
+    null_literal: false,
+    null_sentinel: None,
+    numeric_keys_to_array: false,
+    aliases: None,
+    case_modes: None,
+    duplicate_policy: None,
+    key_notation: None,
+    plus_as_space: true,
+    max_array_len: None,
+    max_value_bytes: None,
+    trim_values: false,
+    radix_literals: false,
+    dedup_arrays: false,
+    bare_flags_as_true: false,
+    allow_semicolons: false,
+    required_keys: None,
+    allowed_keys: None,
+    #[cfg(feature = "encoding_rs")]
+    charset: None,
+    transforms: None,
+    statuses: None,
+    json_errors: false,
+};
+
+impl Default for QueryStConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG.clone()
+    }
+}
+
+macro_rules! option_getters {
+    ($($getter:ident($name:ident): $ty:ty;)*) => {
+        impl QueryStConfig {
+            $(
+                #[doc = concat!("The value set with [`QueryStConfig::", stringify!($name), "`](#method.", stringify!($name), ")")]
+                pub fn $getter(&self) -> $ty {
+                    self.$name
+                }
+            )*
+        }
+    };
+}
+
+option_getters! {
+    get_coerce_bools(coerce_bools): bool;
+    get_coerce_bool_digits(coerce_bool_digits): bool;
+    get_empty_as_none(empty_as_none): bool;
+    get_max_length(max_length): Option<usize>;
+    get_max_nodes(max_nodes): Option<usize>;
+    get_max_depth(max_depth): Option<usize>;
+    get_max_params(max_params): Option<usize>;
+    get_split_on(split_on): Option<char>;
+    get_case_insensitive_keys(case_insensitive_keys): bool;
+    get_deny_unknown(deny_unknown): bool;
+    get_strict_percent_encoding(strict_percent_encoding): bool;
+    get_lossy_utf8(lossy_utf8): bool;
+    get_null_literal(null_literal): bool;
+    get_numeric_keys_to_array(numeric_keys_to_array): bool;
+    get_duplicate_policy(duplicate_policy): Option<DuplicatePolicy>;
+    get_key_notation(key_notation): Option<Notation>;
+    get_plus_as_space(plus_as_space): bool;
+    get_max_array_len(max_array_len): Option<usize>;
+    get_max_value_bytes(max_value_bytes): Option<usize>;
+    get_trim_values(trim_values): bool;
+    get_radix_literals(radix_literals): bool;
+    get_dedup_arrays(dedup_arrays): bool;
+    get_bare_flags_as_true(bare_flags_as_true): bool;
+    get_allow_semicolons(allow_semicolons): bool;
+    get_json_errors(json_errors): bool;
+}
+
+/// Read-only access to the options, ex: to check the config a test or a resource ends up
+/// with. The getters are prefixed with `get_` since the setters take the plain names.
+impl QueryStConfig {
+    /// Whether an [`error_handler`](#method.error_handler) is set
+    pub fn error_handler_set(&self) -> bool {
+        self.ehandler.is_some()
+    }
+
+    /// The number of closures registered with [`transform`](#method.transform)
+    pub fn transform_count(&self) -> usize {
+        self.transforms.as_ref().map_or(0, Vec::len)
+    }
+
+    /// The status set with [`status_for`](#method.status_for) for errors of `kind`
+    pub fn get_status_for(&self, kind: QueryStErrorKind) -> Option<StatusCode> {
+        self.statuses.as_ref()?.get(&kind).copied()
+    }
+
+    /// The key `old` is accepted as, see [`alias`](#method.alias)
+    pub fn get_alias(&self, old: &str) -> Option<&str> {
+        let aliases = self.aliases.as_ref()?;
+        let (_, new) = aliases.iter().find(|(key, _)| key == old)?;
+        Some(new)
+    }
+
+    /// The mode set with [`normalize_case_for`](#method.normalize_case_for) for `key`
+    pub fn get_case_mode(&self, key: &str) -> Option<CaseMode> {
+        self.case_modes.as_ref()?.get(key).copied()
+    }
+
+    /// The suffix set with [`null_sentinel`](#method.null_sentinel)
+    pub fn get_null_sentinel(&self) -> Option<&str> {
+        self.null_sentinel.as_deref()
+    }
+
+    /// The keys set with [`require_keys`](#method.require_keys)
+    pub fn get_required_keys(&self) -> Option<&[String]> {
+        self.required_keys.as_deref()
+    }
+
+    /// The keys set with [`allowed_keys`](#method.allowed_keys)
+    pub fn get_allowed_keys(&self) -> Option<&HashSet<String>> {
+        self.allowed_keys.as_ref()
+    }
+
+    /// The encoding set with [`charset`](#method.charset)
+    #[cfg(feature = "encoding_rs")]
+    pub fn get_charset(&self) -> Option<&'static encoding_rs::Encoding> {
+        self.charset
+    }
+}
+
+/// What to do with keys given more than once, see
+/// [`QueryStConfig::duplicate_policy`](struct.QueryStConfig.html#method.duplicate_policy)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the last value
+    LastWins,
+    /// Keep the first value
+    FirstWins,
+    /// Reject the query with a `DuplicateKey` error
+    Error,
+}
+
+/// How nested keys are written, see
+/// [`QueryStConfig::key_notation`](struct.QueryStConfig.html#method.key_notation)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notation {
+    /// `user[name]=x`, what `queryst` parses
+    Brackets,
+    /// `user.name=x`, along with brackets
+    Dot,
+}
+
+/// Case to rewrite values in, see
+/// [`QueryStConfig::normalize_case_for`](struct.QueryStConfig.html#method.normalize_case_for)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseMode {
+    /// `response_type=Code` gives `code`
+    Lower,
+    /// `response_type=Code` gives `CODE`
+    Upper,
+    /// First letter uppercase and the rest lowercase, `response_type=code` gives `Code`
+    Title,
+}
+
+impl CaseMode {
+    pub(crate) fn apply(self, s: &str) -> String {
+        match self {
+            CaseMode::Lower => s.to_lowercase(),
+            CaseMode::Upper => s.to_uppercase(),
+            CaseMode::Title => {
+                let mut chars = s.chars();
+                match chars.next() {
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect(),
+                    None => String::new(),
+                }
+            }
+        }
+    }
+}
+
+/// Builder for [**QueryStConfig**](struct.QueryStConfig.html), created with
+/// `QueryStConfig::builder()`
+///
+/// Every option works like the `QueryStConfig` method of the same name, calling one twice
+/// keeps the last value. `build` checks the options against each other before handing out
+/// the config.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web_queryst::QueryStConfig;
+///
+/// let config = QueryStConfig::builder()
+///     .max_length(1024)
+///     .coerce_bools(true)
+///     .build()
+///     .unwrap();
+///
+/// assert!(QueryStConfig::builder().coerce_bool_digits(true).build().is_err());
+/// ```
+#[derive(Clone, Default)]
+pub struct QueryStConfigBuilder(QueryStConfig);
+
+impl QueryStConfig {
+    /// Start building a config, see [**QueryStConfigBuilder**](struct.QueryStConfigBuilder.html)
+    pub fn builder() -> QueryStConfigBuilder {
+        QueryStConfigBuilder::default()
+    }
+}
+
+macro_rules! builder_options {
+    ($($name:ident($arg:ident: $ty:ty);)*) => {
+        impl QueryStConfigBuilder {
+            $(
+                #[doc = concat!("See [`QueryStConfig::", stringify!($name), "`](struct.QueryStConfig.html#method.", stringify!($name), ")")]
+                pub fn $name(self, $arg: $ty) -> Self {
+                    QueryStConfigBuilder(self.0.$name($arg))
+                }
+            )*
+        }
+    };
+}
+
+builder_options! {
+    coerce_bools(enabled: bool);
+    coerce_bool_digits(enabled: bool);
+    empty_as_none(enabled: bool);
+    max_length(limit: usize);
+    max_nodes(limit: usize);
+    max_depth(limit: usize);
+    max_params(limit: usize);
+    split_on(delimiter: char);
+    case_insensitive_keys(enabled: bool);
+    deny_unknown(enabled: bool);
+    strict_percent_encoding(enabled: bool);
+    lossy_utf8(enabled: bool);
+    null_literal(enabled: bool);
+    null_sentinel(suffix: &str);
+    numeric_keys_to_array(enabled: bool);
+    duplicate_policy(policy: DuplicatePolicy);
+    key_notation(notation: Notation);
+    plus_as_space(enabled: bool);
+    max_array_len(limit: usize);
+    max_value_bytes(limit: usize);
+    trim_values(enabled: bool);
+    radix_literals(enabled: bool);
+    dedup_arrays(enabled: bool);
+    bare_flags_as_true(enabled: bool);
+    allow_semicolons(enabled: bool);
+    json_errors(enabled: bool);
+    require_keys(keys: &[&str]);
+    allowed_keys(keys: &[&str]);
+}
+
+impl QueryStConfigBuilder {
+    /// See [`QueryStConfig::error_handler`](struct.QueryStConfig.html#method.error_handler)
+    pub fn error_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(QueryStPayloadError, &HttpRequest) -> actix_web::Error + Send + Sync + 'static,
+    {
+        QueryStConfigBuilder(self.0.error_handler(f))
+    }
+
+    /// See [`QueryStConfig::bool_tokens`](struct.QueryStConfig.html#method.bool_tokens)
+    pub fn bool_tokens(self, truthy: &[&str], falsy: &[&str]) -> Self {
+        QueryStConfigBuilder(self.0.bool_tokens(truthy, falsy))
+    }
+
+    /// See [`QueryStConfig::normalize_case_for`](struct.QueryStConfig.html#method.normalize_case_for)
+    pub fn normalize_case_for(self, keys: &[&str], mode: CaseMode) -> Self {
+        QueryStConfigBuilder(self.0.normalize_case_for(keys, mode))
+    }
+
+    /// See [`QueryStConfig::status_for`](struct.QueryStConfig.html#method.status_for)
+    pub fn status_for(self, kind: QueryStErrorKind, status: StatusCode) -> Self {
+        QueryStConfigBuilder(self.0.status_for(kind, status))
+    }
+
+    /// See [`QueryStConfig::transform`](struct.QueryStConfig.html#method.transform)
+    pub fn transform<F>(self, f: F) -> Self
+    where
+        F: Fn(&mut serde_json::Value) + Send + Sync + 'static,
+    {
+        QueryStConfigBuilder(self.0.transform(f))
+    }
+
+    /// See [`QueryStConfig::charset`](struct.QueryStConfig.html#method.charset)
+    #[cfg(feature = "encoding_rs")]
+    pub fn charset(self, encoding: &'static encoding_rs::Encoding) -> Self {
+        QueryStConfigBuilder(self.0.charset(encoding))
+    }
+
+    /// See [`QueryStConfig::extend`](struct.QueryStConfig.html#method.extend)
+    pub fn extend(self, other: &QueryStConfig) -> Self {
+        QueryStConfigBuilder(self.0.extend(other))
+    }
+
+    /// See [`QueryStConfig::alias`](struct.QueryStConfig.html#method.alias)
+    pub fn alias(self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        QueryStConfigBuilder(self.0.alias(old, new))
+    }
+
+    /// Check the options against each other and build the config
+    pub fn build(self) -> Result<QueryStConfig, QueryStConfigError> {
+        let config = self.0;
+        if config.coerce_bool_digits && !config.coerce_bools {
+            return Err(QueryStConfigError::BoolDigitsWithoutBools);
+        }
+        if config.bool_tokens.is_some() && !config.coerce_bools {
+            return Err(QueryStConfigError::BoolTokensWithoutBools);
+        }
+        if config.empty_as_none && config.split_on.is_some() {
+            return Err(QueryStConfigError::EmptyAsNoneWithSplitOn);
+        }
+        Ok(config)
+    }
+}
+
+/// Options of a [**QueryStConfigBuilder**](struct.QueryStConfigBuilder.html) that don't
+/// make sense together
+#[derive(Debug, Display, PartialEq, Eq)]
+pub enum QueryStConfigError {
+    /// `coerce_bool_digits` has no effect without `coerce_bools`
+    #[display(fmt = "QueryStConfig coerce_bool_digits requires coerce_bools")]
+    BoolDigitsWithoutBools,
+    /// `bool_tokens` has no effect without `coerce_bools`
+    #[display(fmt = "QueryStConfig bool_tokens requires coerce_bools")]
+    BoolTokensWithoutBools,
+    /// `empty_as_none` would turn the empty segments of split values into `null`
+    #[display(fmt = "QueryStConfig empty_as_none can't be used with split_on")]
+    EmptyAsNoneWithSplitOn,
+}
+
+impl std::error::Error for QueryStConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extend() {
+        let base = QueryStConfig::default()
+            .max_length(100)
+            .max_depth(3)
+            .coerce_bools(true)
+            .alias("q", "query")
+            .alias("p", "page");
+        let route = QueryStConfig::default()
+            .max_depth(5)
+            .plus_as_space(false)
+            .alias("p", "per_page")
+            .error_handler(|e, _| {
+                InternalError::from_response(e, HttpResponse::Conflict().finish()).into()
+            })
+            .extend(&base);
+
+        assert_eq!(route.max_length, Some(100));
+        assert_eq!(route.max_depth, Some(5));
+        assert!(route.coerce_bools);
+        assert!(!route.plus_as_space);
+        assert!(route.ehandler.is_some());
+        assert_eq!(route.get_alias("q"), Some("query"));
+        assert_eq!(route.get_alias("p"), Some("per_page"));
+
+        let config = QueryStConfig::default().extend(&QueryStConfig::default());
+        assert!(config.plus_as_space);
+        assert!(config.ehandler.is_none());
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = QueryStConfig::builder()
+            .max_length(10)
+            .max_length(20)
+            .split_on(',')
+            .error_handler(|e, _| {
+                InternalError::from_response(e, HttpResponse::Conflict().finish()).into()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(config.max_length, Some(20));
+        assert_eq!(config.split_on, Some(','));
+        assert!(config.ehandler.is_some());
+
+        assert_eq!(
+            QueryStConfig::builder()
+                .coerce_bool_digits(true)
+                .build()
+                .err(),
+            Some(QueryStConfigError::BoolDigitsWithoutBools)
+        );
+        assert!(QueryStConfig::builder()
+            .coerce_bool_digits(true)
+            .coerce_bools(true)
+            .build()
+            .is_ok());
+        assert_eq!(
+            QueryStConfig::builder()
+                .split_on(',')
+                .empty_as_none(true)
+                .build()
+                .err(),
+            Some(QueryStConfigError::EmptyAsNoneWithSplitOn)
+        );
+        assert!(QueryStConfig::builder().empty_as_none(true).build().is_ok());
+    }
+
+    #[test]
+    fn test_getters() {
+        let config = QueryStConfig::default();
+        assert!(!config.error_handler_set());
+        assert_eq!(config.get_max_length(), None);
+        assert!(config.get_plus_as_space());
+        assert_eq!(config.transform_count(), 0);
+        assert_eq!(config.get_alias("q"), None);
+
+        let config = QueryStConfig::builder()
+            .max_length(20)
+            .coerce_bools(true)
+            .split_on(',')
+            .duplicate_policy(DuplicatePolicy::LastWins)
+            .null_sentinel("__null")
+            .require_keys(&["id"])
+            .alias("q", "query")
+            .normalize_case_for(&["kind"], CaseMode::Lower)
+            .status_for(QueryStErrorKind::TooLarge, StatusCode::BAD_REQUEST)
+            .transform(|_| {})
+            .error_handler(|e, _| e.into())
+            .build()
+            .unwrap();
+        assert!(config.error_handler_set());
+        assert_eq!(config.get_max_length(), Some(20));
+        assert!(config.get_coerce_bools());
+        assert!(!config.get_coerce_bool_digits());
+        assert_eq!(config.get_split_on(), Some(','));
+        assert_eq!(
+            config.get_duplicate_policy(),
+            Some(DuplicatePolicy::LastWins)
+        );
+        assert_eq!(config.get_null_sentinel(), Some("__null"));
+        assert_eq!(config.get_required_keys(), Some(&["id".to_owned()][..]));
+        assert_eq!(config.get_allowed_keys(), None);
+        assert_eq!(config.get_alias("q"), Some("query"));
+        assert_eq!(config.get_case_mode("kind"), Some(CaseMode::Lower));
+        assert_eq!(
+            config.get_status_for(QueryStErrorKind::TooLarge),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(config.get_status_for(QueryStErrorKind::UnknownKey), None);
+        assert_eq!(config.transform_count(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, web::HttpRequest, FromRequest};
+use serde::de;
+
+use crate::{parse_req, Extraction, QuerySt, QueryStConfig};
+
+/// Extract information from the request's query using `queryst`, falling back to
+/// `T::default()` when the query string is empty.
+///
+/// Non-empty queries behave exactly like [**QuerySt**](struct.QuerySt.html), including
+/// errors and the configured error handler. This suits routes that also answer requests
+/// without a query (ex: `OPTIONS`) without making every field an `Option`.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::DefaultQuerySt;
+/// use serde::Deserialize;
+///
+/// #[derive(Default, Deserialize)]
+/// pub struct Pagination {
+///    page: u32,
+/// }
+///
+/// // `/items` is called with page 0, `/items?page=x` is rejected.
+/// async fn index(pagination: DefaultQuerySt<Pagination>) -> String {
+///     format!("Page {}", pagination.page)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/items").route(web::get().to(index)));
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct DefaultQuerySt<T>(pub T);
+
+impl_wrapper!(DefaultQuerySt<T>, T);
+
+impl<T> FromRequest for DefaultQuerySt<T>
+where
+    T: de::DeserializeOwned + Default,
+{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, actix_web::Error>>;
+    type Config = QueryStConfig;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let mut extraction = Extraction::start(req, "DefaultQuerySt", req.query_string().len());
+        if req.query_string().is_empty() {
+            extraction.finish(req, &Ok(()));
+            return ready(Ok(DefaultQuerySt(T::default())));
+        }
+
+        let config = QueryStConfig::from_req(req);
+        let r = extraction.run(|extraction| {
+            parse_req(req, config).and_then(|value| {
+                extraction.parsed(&value);
+                QuerySt::from_value_with(value, config)
+            })
+        });
+        extraction.finish(req, &r);
+        ready(
+            r.map(|QuerySt(inner)| DefaultQuerySt(inner))
+                .map_err(|e| config.handle_error(e, req)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, Default, PartialEq)]
+    struct Pagination {
+        page: u32,
+        per_page: u32,
+    }
+
+    #[actix_rt::test]
+    async fn test_default_request_extract() {
+        let req = TestRequest::with_uri("/items").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = DefaultQuerySt::<Pagination>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.into_inner(), Pagination::default());
+
+        let req = TestRequest::with_uri("/items?page=2&per_page=10").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = DefaultQuerySt::<Pagination>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(
+            s.into_inner(),
+            Pagination {
+                page: 2,
+                per_page: 10
+            }
+        );
+
+        let req = TestRequest::with_uri("/items?page=2").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(DefaultQuerySt::<Pagination>::from_request(&req, &mut pl)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Discovery of the field and variant names a type expects, used to reject unknown query keys
+//! and to match enum variants without regard to case.
+
+use std::fmt;
+
+use serde::de::{self, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// Field names of `T` if it deserializes from a struct, `None` otherwise.
+///
+/// This asks `T` to deserialize itself from a deserializer that only records the fields
+/// passed to `deserialize_struct`, then bails out.
+pub(crate) fn struct_fields<'de, T>() -> Option<&'static [&'static str]>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut fields = None;
+    let _ = T::deserialize(FieldsDeserializer {
+        fields: &mut fields,
+        variants: &mut None,
+    });
+    fields
+}
+
+/// Variant names of `T` if it deserializes from an enum, `None` otherwise.
+pub(crate) fn enum_variants<'de, T>() -> Option<&'static [&'static str]>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut variants = None;
+    let _ = T::deserialize(FieldsDeserializer {
+        fields: &mut None,
+        variants: &mut variants,
+    });
+    variants
+}
+
+struct FieldsDeserializer<'a> {
+    fields: &'a mut Option<&'static [&'static str]>,
+    variants: &'a mut Option<&'static [&'static str]>,
+}
+
+#[derive(Debug)]
+struct Done;
+
+impl fmt::Display for Done {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("done")
+    }
+}
+
+impl std::error::Error for Done {}
+
+impl de::Error for Done {
+    fn custom<M: fmt::Display>(_: M) -> Self {
+        Done
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldsDeserializer<'a> {
+    type Error = Done;
+
+    fn deserialize_any<V>(self, _: V) -> Result<V::Value, Done>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Done)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Done>
+    where
+        V: Visitor<'de>,
+    {
+        *self.fields = Some(fields);
+        Err(Done)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Done>
+    where
+        V: Visitor<'de>,
+    {
+        *self.variants = Some(variants);
+        Err(Done)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Info {
+        id: u64,
+        #[serde(rename = "q")]
+        query: String,
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        assert_eq!(struct_fields::<Info>(), Some(&["id", "q"][..]));
+        assert_eq!(struct_fields::<HashMap<String, String>>(), None);
+        assert_eq!(struct_fields::<serde_json::Value>(), None);
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Kind {
+        Token,
+        #[serde(rename = "auth_code")]
+        Code,
+    }
+
+    #[test]
+    fn test_enum_variants() {
+        assert_eq!(enum_variants::<Kind>(), Some(&["Token", "auth_code"][..]));
+        assert_eq!(enum_variants::<Info>(), None);
+        assert_eq!(struct_fields::<Kind>(), None);
+    }
+}
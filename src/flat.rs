@@ -0,0 +1,304 @@
+//! Deserialization straight from the pairs of a flat query string, without building the
+//! intermediate `serde_json::Value`.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashSet;
+
+use percent_encoding::percent_decode_str;
+use serde::de::{self, value::MapDeserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::normalize;
+
+/// Deserialize `T` from a query without brackets or repeated keys.
+///
+/// Returns `None` if the query needs the full `queryst` parser. Decoding and numeric
+/// coercion follow `queryst::parse` and `coerced`, so both paths agree.
+pub(crate) fn from_query<T>(query_str: &str) -> Option<Result<T, PassError>>
+where
+    T: de::DeserializeOwned,
+{
+    // `queryst` decodes the whole query before splitting it into pairs
+    let decoded = if query_str.contains('+') {
+        Cow::Owned(decode(&query_str.replace('+', " ")).into_owned())
+    } else {
+        decode(query_str)
+    };
+    if decoded.contains(['[', ']']) {
+        return None;
+    }
+
+    let mut keys = HashSet::new();
+    let mut pairs = Vec::new();
+    for part in decoded.split('&') {
+        let (key, value) = match part.find('=') {
+            Some(pos) => (&part[..pos], Some(&part[pos + 1..])),
+            None => (part, None),
+        };
+        if key.is_empty() {
+            continue;
+        }
+        let key = decode(key);
+        if !keys.insert(key.clone()) {
+            return None;
+        }
+        pairs.push((key, ValueDeserializer(value.map(decode))));
+    }
+
+    let empty = pairs.is_empty();
+    Some(single_pass(empty, || {
+        serde_path_to_error::deserialize(MapDeserializer::new(pairs.into_iter()))
+    }))
+}
+
+thread_local! {
+    /// Whether `ValueDeserializer::deserialize_any` gave a number for a string since the
+    /// last `single_pass` started
+    static GUESSED_NUMBER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Failure of a single-pass deserialization
+#[derive(Debug)]
+pub(crate) struct PassError {
+    pub(crate) error: serde_path_to_error::Error<serde_json::Error>,
+    /// Whether a number-like string was given as a number to a type that didn't say what it
+    /// expects, which `coerced` would retry with the string
+    pub(crate) guessed_number: bool,
+    /// Whether the query had no pairs
+    pub(crate) empty: bool,
+}
+
+/// Run the single-pass deserialization `f` of a query, `empty` if it has no pairs
+pub(crate) fn single_pass<T>(
+    empty: bool,
+    f: impl FnOnce() -> Result<T, serde_path_to_error::Error<serde_json::Error>>,
+) -> Result<T, PassError> {
+    GUESSED_NUMBER.with(|guessed| guessed.set(false));
+    f().map_err(|error| PassError {
+        error,
+        guessed_number: GUESSED_NUMBER.with(Cell::get),
+        empty,
+    })
+}
+
+pub(crate) fn decode(s: &str) -> Cow<'_, str> {
+    percent_decode_str(s).decode_utf8_lossy()
+}
+
+/// Deserializer for a single decoded value, `None` standing for a key without `=`
+pub(crate) struct ValueDeserializer<'a>(pub(crate) Option<Cow<'a, str>>);
+
+impl<'de, 'a> IntoDeserializer<'de, serde_json::Error> for ValueDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'a> ValueDeserializer<'a> {
+    fn deserialize_number<'de, V>(self, visitor: V) -> Result<V::Value, serde_json::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0.as_deref().and_then(normalize::parse_number) {
+            Some(number) => de::Deserializer::deserialize_any(number, visitor),
+            None => de::Deserializer::deserialize_any(self, visitor),
+        }
+    }
+}
+
+macro_rules! deserialize_numbers {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.deserialize_number(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_unit(),
+            Some(s) => match normalize::parse_number(&s) {
+                Some(number) => {
+                    GUESSED_NUMBER.with(|guessed| guessed.set(true));
+                    de::Deserializer::deserialize_any(number, visitor)
+                }
+                None => match s {
+                    Cow::Borrowed(s) => visitor.visit_str(s),
+                    Cow::Owned(s) => visitor.visit_string(s),
+                },
+            },
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(Cow::Borrowed(s)) => visitor.visit_str(s),
+            Some(Cow::Owned(s)) => visitor.visit_string(s),
+            None => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(s) => visitor.visit_enum(IntoDeserializer::<Self::Error>::into_deserializer(s)),
+            None => Err(de::Error::invalid_type(de::Unexpected::Unit, &"a string")),
+        }
+    }
+
+    deserialize_numbers! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+    }
+
+    forward_to_deserialize_any! {
+        bool bytes byte_buf unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Kind {
+        Token,
+        Code,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Flat {
+        id: u64,
+        ratio: f64,
+        name: String,
+        kind: Kind,
+        nick: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Named {
+        name: String,
+        zip: String,
+    }
+
+    #[test]
+    fn test_flat_query() {
+        let s: Flat = from_query("id=64&ratio=1.5&name=a+b%20c&kind=Code&nick=x")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            s,
+            Flat {
+                id: 64,
+                ratio: 1.5,
+                name: "a b c".to_owned(),
+                kind: Kind::Code,
+                nick: Some("x".to_owned()),
+            }
+        );
+        assert!(from_query::<Flat>("id=64").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_numeric_strings() {
+        let s: Named = from_query("name=1.5&zip=12345").unwrap().unwrap();
+        assert_eq!((s.name.as_str(), s.zip.as_str()), ("1.5", "12345"));
+    }
+
+    #[test]
+    fn test_same_as_queryst() {
+        for query in &[
+            "a=1&b=x&&c",
+            "a=%2526&b=007",
+            "=x&a=-1e3",
+            "a=x%26b%3Dy",
+            "",
+        ] {
+            let fast: Value = from_query(query).unwrap().unwrap();
+            let value = queryst::parse(query).unwrap();
+            let value: Value = crate::coerced::deserialize(&value).unwrap();
+            assert_eq!(fast, value, "{}", query);
+        }
+        assert_eq!(
+            from_query::<Value>("a=1").unwrap().unwrap(),
+            json!({"a": 1})
+        );
+    }
+
+    #[test]
+    fn test_needs_full_parser() {
+        for query in &["a[]=1", "a%5Bb%5D=1", "a=1&a=2"] {
+            assert!(from_query::<Value>(query).is_none(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use actix_web::dev::{Decompress, Payload};
+use actix_web::http::header;
+use actix_web::{web::HttpRequest, FromRequest};
+use futures_util::StreamExt;
+use serde::de;
+
+use crate::{parse_value_with, BoxFuture, Extraction, QuerySt, QueryStConfig, QueryStPayloadError};
+
+/// Extract information from a form-encoded request body using `queryst`.
+///
+/// The body is read up to the limit of the
+/// [**QueryStFormConfig**](struct.QueryStFormConfig.html) and then goes through the same
+/// parsing, coercions and error handling as the query of a
+/// [**QuerySt**](struct.QuerySt.html), following the
+/// [**QueryStConfig**](struct.QueryStConfig.html) of the request. The content type of the
+/// request is not checked, and errors passed to the error handler carry the body as their
+/// [`query`](enum.QueryStPayloadError.html#method.query). Invalid UTF-8 in the body is replaced with `U+FFFD` unless `lossy_utf8` is
+/// disabled.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::QueryStForm;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Login {
+///    user: String,
+///    remember: Option<String>,
+/// }
+///
+/// // `user=test&remember=on` posted as the body
+/// async fn login(form: QueryStForm<Login>) -> String {
+///     format!("Welcome {}!", form.user)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/login").route(web::post().to(login)));
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct QueryStForm<T>(pub T);
+
+impl_wrapper!(QueryStForm<T>, T);
+
+impl<T> FromRequest for QueryStForm<T>
+where
+    T: de::DeserializeOwned + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = BoxFuture<Result<Self, actix_web::Error>>;
+    type Config = QueryStFormConfig;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let config = QueryStConfig::from_req(req).clone();
+        let limit = req
+            .app_data::<QueryStFormConfig>()
+            .unwrap_or(&DEFAULT_FORM_CONFIG)
+            .limit;
+        let length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse::<usize>().ok());
+        let stream = Decompress::from_headers(payload.take(), req.headers());
+        let req = req.clone();
+
+        Box::pin(async move {
+            let body = read_body(stream, length, limit, config.lossy_utf8).await;
+            let mut extraction =
+                Extraction::start(&req, "QueryStForm", body.as_ref().map_or(0, String::len));
+            let (result, body) = match body {
+                Ok(body) => {
+                    let result = extraction.run(|extraction| {
+                        parse_value_with(&body, &config).and_then(|value| {
+                            extraction.parsed(&value);
+                            QuerySt::from_value_with(value, &config)
+                        })
+                    });
+                    (result, body)
+                }
+                Err(e) => (Err(e), String::new()),
+            };
+            extraction.finish(&req, &result);
+            result
+                .map(|QuerySt(inner)| QueryStForm(inner))
+                .map_err(|e| config.handle_error_with(e, &req, &body))
+        })
+    }
+}
+
+/// Read the whole body as UTF-8, failing as soon as it exceeds `limit`
+///
+/// Invalid UTF-8 is replaced with `U+FFFD` if `lossy`, and rejected otherwise.
+async fn read_body(
+    mut stream: Decompress<Payload>,
+    length: Option<usize>,
+    limit: usize,
+    lossy: bool,
+) -> Result<String, QueryStPayloadError> {
+    if let Some(length) = length.filter(|&length| length > limit) {
+        return Err(QueryStPayloadError::Overflow {
+            length,
+            limit,
+            query: None,
+        });
+    }
+    let mut body = Vec::with_capacity(length.unwrap_or_default());
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| QueryStPayloadError::Payload(e, None))?;
+        if body.len() + chunk.len() > limit {
+            return Err(QueryStPayloadError::Overflow {
+                length: body.len() + chunk.len(),
+                limit,
+                query: None,
+            });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    match String::from_utf8(body) {
+        Ok(body) => Ok(body),
+        Err(e) if lossy => Ok(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        Err(e) => Err(QueryStPayloadError::InvalidEncoding(
+            format!(
+                "invalid UTF-8 in the body at byte {}",
+                e.utf8_error().valid_up_to()
+            ),
+            None,
+        )),
+    }
+}
+
+/// QueryStForm extractor configuration, holding the limit on the body size
+///
+/// The usual options are taken from the [**QueryStConfig**](struct.QueryStConfig.html) of
+/// the request, this only adds the limit.
+#[derive(Clone)]
+pub struct QueryStFormConfig {
+    limit: usize,
+}
+
+const DEFAULT_FORM_CONFIG: QueryStFormConfig = QueryStFormConfig { limit: 16_384 };
+
+impl QueryStFormConfig {
+    /// Reject bodies longer than `limit` bytes with an `Overflow` error, 16kB by default
+    ///
+    /// The limit applies to the decompressed body. `max_length` of the
+    /// [**QueryStConfig**](struct.QueryStConfig.html) is checked as well once the body is read.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl Default for QueryStFormConfig {
+    fn default() -> Self {
+        DEFAULT_FORM_CONFIG.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{QueryStDiagnostics, QueryStErrorKind};
+
+    #[derive(Deserialize, Debug)]
+    struct Id {
+        id: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_form_extract() {
+        let (req, mut pl) = TestRequest::post().set_payload("id=test").to_http_parts();
+        let s = QueryStForm::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.id, "test");
+
+        let (req, mut pl) = TestRequest::post()
+            .set_payload("user[name]=a+b&user[ids][]=1")
+            .to_http_parts();
+        let s = QueryStForm::<serde_json::Value>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(
+            s.0,
+            serde_json::json!({"user": {"name": "a b", "ids": [1]}})
+        );
+
+        let (req, mut pl) = TestRequest::post().set_payload("id[]=x").to_http_parts();
+        let err = QueryStForm::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            req.extensions().get::<QueryStDiagnostics>(),
+            Some(&QueryStDiagnostics {
+                query_len: 6,
+                keys: 1,
+                error: Some(QueryStErrorKind::TypeMismatch),
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_form_limits() {
+        let (req, mut pl) = TestRequest::post()
+            .app_data(QueryStFormConfig::default().limit(4))
+            .set_payload("id=test")
+            .to_http_parts();
+        let err = QueryStForm::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let (req, mut pl) = TestRequest::post()
+            .set_payload(&b"id=a\xff"[..])
+            .to_http_parts();
+        let s = QueryStForm::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.id, "a\u{FFFD}");
+
+        for body in &[&b"id=a\xff"[..], b"id=a%FF"] {
+            let (req, mut pl) = TestRequest::post()
+                .app_data(
+                    QueryStConfig::default()
+                        .lossy_utf8(false)
+                        .error_handler(|e, _| {
+                            assert!(matches!(e, QueryStPayloadError::InvalidEncoding(_, _)));
+                            e.into()
+                        }),
+                )
+                .set_payload(*body)
+                .to_http_parts();
+            assert!(QueryStForm::<Id>::from_request(&req, &mut pl)
+                .await
+                .is_err());
+        }
+
+        let (req, mut pl) = TestRequest::post()
+            .app_data(QueryStConfig::default().error_handler(|e, _| {
+                assert_eq!(e.query(), Some("id[]=x"));
+                e.into()
+            }))
+            .set_payload("id[]=x")
+            .to_http_parts();
+        assert!(QueryStForm::<Id>::from_request(&req, &mut pl)
+            .await
+            .is_err());
+    }
+}
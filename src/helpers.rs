@@ -0,0 +1,412 @@
+//! Functions to use with `#[serde(deserialize_with = "...")]` on fields of the target type.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde::de::{self, IntoDeserializer, Visitor};
+
+use crate::fields;
+
+/// Deserialize a unit-only enum from a string, matching its variant names without regard to
+/// ASCII case
+///
+/// serde matches variant names exactly, so `response_type=code` doesn't select a `Code`
+/// variant. Values matching no variant fail with serde's usual unknown variant error.
+/// Renamed variants are matched against their new name.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web_queryst::{ci_enum, QuerySt};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// pub enum ResponseType {
+///    Token,
+///    Code
+/// }
+///
+/// #[derive(Deserialize)]
+/// pub struct AuthRequest {
+///    #[serde(deserialize_with = "ci_enum")]
+///    response_type: ResponseType,
+/// }
+///
+/// let req = QuerySt::<AuthRequest>::from_query("response_type=code").unwrap();
+/// assert_eq!(req.response_type, ResponseType::Code);
+/// ```
+pub fn ci_enum<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: de::Deserialize<'de>,
+{
+    let value = <String as de::Deserialize>::deserialize(deserializer)?;
+    let variant = fields::enum_variants::<T>()
+        .and_then(|variants| variants.iter().find(|v| v.eq_ignore_ascii_case(&value)));
+    match variant {
+        Some(variant) => T::deserialize((*variant).into_deserializer()),
+        None => T::deserialize(value.into_deserializer()),
+    }
+}
+
+/// Deserialize a field from a string or a number with its `FromStr` implementation
+///
+/// Numeric coercion leaves values like `007` as strings, and the same type may be
+/// deserialized from JSON elsewhere with actual numbers. This accepts both for a single field.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web_queryst::{coerce_number, QuerySt};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Account {
+///    #[serde(deserialize_with = "coerce_number")]
+///    id: u64,
+///    code: String,
+/// }
+///
+/// let account = QuerySt::<Account>::from_query("id=007&code=x").unwrap();
+/// assert_eq!(account.id, 7);
+/// ```
+pub fn coerce_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    deserializer.deserialize_any(FromStrVisitor(PhantomData))
+}
+
+/// Deserialize a boolean from `true`/`false`, `1`/`0` or an actual boolean, ignoring ASCII
+/// case, without enabling `coerce_bools` for the whole query
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web_queryst::{coerce_bool, QuerySt};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Listing {
+///    #[serde(deserialize_with = "coerce_bool")]
+///    archived: bool,
+/// }
+///
+/// let listing = QuerySt::<Listing>::from_query("archived=True").unwrap();
+/// assert!(listing.archived);
+/// ```
+pub fn coerce_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    deserializer
+        .deserialize_any(FromStrVisitor(PhantomData::<BoolStr>))
+        .map(|BoolStr(b)| b)
+}
+
+/// Like [`coerce_number`](fn.coerce_number.html) for an `Option`, an empty value or a key
+/// without value giving `None`
+///
+/// Add `#[serde(default)]` so a missing key gives `None` as well.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web_queryst::{coerce_opt, QuerySt};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Page {
+///    #[serde(default, deserialize_with = "coerce_opt")]
+///    limit: Option<u32>,
+/// }
+///
+/// assert_eq!(QuerySt::<Page>::from_query("limit=10").unwrap().limit, Some(10));
+/// assert_eq!(QuerySt::<Page>::from_query("limit=").unwrap().limit, None);
+/// assert_eq!(QuerySt::<Page>::from_query("").unwrap().limit, None);
+/// ```
+pub fn coerce_opt<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    deserializer.deserialize_option(OptionVisitor(PhantomData))
+}
+
+/// Parse strings, numbers and booleans with `FromStr`
+struct FromStrVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for FromStrVisitor<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string or a number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<T, E> {
+        self.visit_str(if v { "true" } else { "false" })
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        self.visit_str(&v.to_string())
+    }
+}
+
+struct OptionVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for OptionVisitor<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an optional string or number")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        if v.is_empty() {
+            return Ok(None);
+        }
+        FromStrVisitor(PhantomData).visit_str(v).map(Some)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        FromStrVisitor(PhantomData).visit_bool(v).map(Some)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        FromStrVisitor(PhantomData).visit_i64(v).map(Some)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        FromStrVisitor(PhantomData).visit_u64(v).map(Some)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        FromStrVisitor(PhantomData).visit_f64(v).map(Some)
+    }
+}
+
+/// A boolean parsed from `true`, `false`, `1` or `0`, ignoring ASCII case
+struct BoolStr(bool);
+
+impl FromStr for BoolStr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if s.eq_ignore_ascii_case("true") || s == "1" {
+            Ok(BoolStr(true))
+        } else if s.eq_ignore_ascii_case("false") || s == "0" {
+            Ok(BoolStr(false))
+        } else {
+            Err(format!("invalid boolean: {}", s))
+        }
+    }
+}
+
+/// Deserialize a [`Duration`](std::time::Duration) from a `humantime` string (ex: `1h30m`,
+/// `15s`, `2days`), requires the `humantime` feature
+///
+/// A value without a unit is rejected, like `humantime` does.
+///
+/// ## Example
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use actix_web_queryst::{deserialize_humantime_duration, QuerySt};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Cache {
+///    #[serde(deserialize_with = "deserialize_humantime_duration")]
+///    ttl: Duration,
+/// }
+///
+/// let cache = QuerySt::<Cache>::from_query("ttl=1h30m").unwrap();
+/// assert_eq!(cache.ttl, Duration::from_secs(90 * 60));
+/// ```
+#[cfg(feature = "humantime")]
+pub fn deserialize_humantime_duration<'de, D>(
+    deserializer: D,
+) -> Result<std::time::Duration, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let value = <String as de::Deserialize>::deserialize(deserializer)?;
+    humantime::parse_duration(&value).map_err(de::Error::custom)
+}
+
+/// Deserialize an RFC 3339 timestamp (ex: `2023-01-01T00:00:00Z`) into a `chrono` date,
+/// keeping its offset, requires the `chrono` feature
+///
+/// A `+` in the offset has to be sent as `%2B`, or it is decoded as a space.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web_queryst::{deserialize_rfc3339, QuerySt};
+/// use chrono::{DateTime, FixedOffset};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Events {
+///    #[serde(deserialize_with = "deserialize_rfc3339")]
+///    since: DateTime<FixedOffset>,
+/// }
+///
+/// let events = QuerySt::<Events>::from_query("since=2023-01-01T10:00:00%2B02:00").unwrap();
+/// assert_eq!(events.since.timestamp(), 1672560000);
+/// ```
+#[cfg(feature = "chrono")]
+pub fn deserialize_rfc3339<'de, D>(
+    deserializer: D,
+) -> Result<chrono::DateTime<chrono::FixedOffset>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let value = <String as de::Deserialize>::deserialize(deserializer)?;
+    chrono::DateTime::parse_from_rfc3339(&value).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::QuerySt;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum ResponseType {
+        Token,
+        #[serde(rename = "auth_code")]
+        Code,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct AuthRequest {
+        #[serde(deserialize_with = "ci_enum")]
+        response_type: ResponseType,
+    }
+
+    #[actix_rt::test]
+    async fn test_ci_enum() {
+        for (query, expected) in &[
+            ("response_type=token", ResponseType::Token),
+            ("response_type=TOKEN", ResponseType::Token),
+            ("response_type=Token", ResponseType::Token),
+            ("response_type=Auth_Code", ResponseType::Code),
+        ] {
+            let s = QuerySt::<AuthRequest>::from_query(query).unwrap();
+            assert_eq!(&s.response_type, expected);
+        }
+
+        let err = QuerySt::<AuthRequest>::from_query("response_type=code").unwrap_err();
+        assert!(err.to_string().contains("unknown variant"), "{}", err);
+    }
+    #[derive(Debug, Deserialize)]
+    struct Coerced {
+        #[serde(deserialize_with = "coerce_number")]
+        id: u64,
+        #[serde(deserialize_with = "coerce_bool")]
+        active: bool,
+        #[serde(default, deserialize_with = "coerce_opt")]
+        limit: Option<u32>,
+        code: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_coerce_adapters() {
+        let s = QuerySt::<Coerced>::from_query("id=007&active=1&limit=5&code=abc").unwrap();
+        assert_eq!((s.id, s.active, s.limit), (7, true, Some(5)));
+        assert_eq!(s.code, "abc");
+
+        let s = QuerySt::<Coerced>::from_query("id=64&active=FALSE&limit=&code=x").unwrap();
+        assert_eq!((s.id, s.active, s.limit), (64, false, None));
+        let s = QuerySt::<Coerced>::from_query("id=1&active=true&code=x").unwrap();
+        assert_eq!(s.limit, None);
+
+        let value = serde_json::json!({"id": "12", "active": true, "limit": 3, "code": "x"});
+        let s: Coerced = serde_json::from_value(value).unwrap();
+        assert_eq!((s.id, s.limit), (12, Some(3)));
+
+        for query in [
+            "id=x&active=1&code=x",
+            "id=1&active=yes&code=x",
+            "id=-1&active=1&code=x",
+        ] {
+            assert!(QuerySt::<Coerced>::from_query(query).is_err(), "{}", query);
+        }
+    }
+
+    #[cfg(feature = "humantime")]
+    #[actix_rt::test]
+    async fn test_humantime_duration() {
+        use std::time::Duration;
+
+        #[derive(Debug, Deserialize)]
+        struct Cache {
+            #[serde(deserialize_with = "deserialize_humantime_duration")]
+            ttl: Duration,
+        }
+
+        let s = QuerySt::<Cache>::from_query("ttl=1h30m").unwrap();
+        assert_eq!(s.ttl, Duration::from_secs(5400));
+        assert!(QuerySt::<Cache>::from_query("ttl=soon").is_err());
+        assert!(QuerySt::<Cache>::from_query("ttl=30").is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[actix_rt::test]
+    async fn test_rfc3339() {
+        use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+
+        #[derive(Debug, Deserialize)]
+        struct Events {
+            #[serde(deserialize_with = "deserialize_rfc3339")]
+            at: DateTime<FixedOffset>,
+        }
+
+        let s = QuerySt::<Events>::from_query("at=2023-01-01T00:00:00Z").unwrap();
+        assert_eq!(s.at, Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap());
+        assert!(QuerySt::<Events>::from_query("at=2023-01-01").is_err());
+    }
+}
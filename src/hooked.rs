@@ -0,0 +1,228 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use actix_web::{dev::Payload, web::HttpRequest, FromRequest};
+use serde::de;
+
+use crate::{normalize_with, parse_req, Extraction, QuerySt, QueryStConfig, QueryStPayloadError};
+
+/// Boxed future returned by the hook of an [**AsyncQueryStConfig**](struct.AsyncQueryStConfig.html)
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+type AsyncHook = Arc<
+    dyn Fn(
+            serde_json::Value,
+            &HttpRequest,
+        ) -> BoxFuture<Result<serde_json::Value, QueryStPayloadError>>
+        + Send
+        + Sync,
+>;
+
+/// Extract information from the request's query using `queryst`, running the asynchronous
+/// hook of the [**AsyncQueryStConfig**](struct.AsyncQueryStConfig.html) before deserialization.
+///
+/// Parsing, coercion and error handling follow the
+/// [**QueryStConfig**](struct.QueryStConfig.html) of the request like for
+/// [**QuerySt**](struct.QuerySt.html). The hook receives the coerced value, where values that
+/// look like numbers are still strings, and what it returns is deserialized into `T`; an
+/// error from the hook goes through the error handler as well. Without a hook this works like
+/// `QuerySt`, with a boxed future.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::{AsyncQuerySt, AsyncQueryStConfig};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Profile {
+///    user: String,
+/// }
+///
+/// async fn index(profile: AsyncQuerySt<Profile>) -> String {
+///     format!("Profile of {}", profile.user)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/profile")
+///             .app_data(AsyncQueryStConfig::default().hook(|mut value, _| {
+///                 Box::pin(async move {
+///                     // ex: look the user up in a database
+///                     value["user"] = "resolved".into();
+///                     Ok(value)
+///                 })
+///             }))
+///             .route(web::get().to(index)),
+///     );
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct AsyncQuerySt<T>(pub T);
+
+impl_wrapper!(AsyncQuerySt<T>, T);
+
+impl<T> FromRequest for AsyncQuerySt<T>
+where
+    T: de::DeserializeOwned + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = BoxFuture<Result<Self, actix_web::Error>>;
+    type Config = AsyncQueryStConfig;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = QueryStConfig::from_req(req).clone();
+        let hook = req
+            .app_data::<AsyncQueryStConfig>()
+            .and_then(|c| c.hook.clone());
+        let mut extraction = Extraction::start(req, "AsyncQuerySt", req.query_string().len());
+        let value = extraction.run(|extraction| {
+            parse_req(req, &config).and_then(|mut value| {
+                extraction.parsed(&value);
+                normalize_with(&mut value, &config)?;
+                Ok(value)
+            })
+        });
+        let req = req.clone();
+
+        Box::pin(async move {
+            let value = match (value, hook) {
+                (Ok(value), Some(hook)) => hook(value, &req).await,
+                (value, _) => value,
+            };
+            let r = extraction
+                .run(|_| value.and_then(|value| QuerySt::from_normalized(value, &config)));
+            extraction.finish(&req, &r);
+            r.map(|QuerySt(inner)| AsyncQuerySt(inner))
+                .map_err(|e| config.handle_error(e, &req))
+        })
+    }
+}
+
+/// AsyncQuerySt extractor configuration, holding the hook run before deserialization
+///
+/// The usual options are taken from the [**QueryStConfig**](struct.QueryStConfig.html) of
+/// the request, this only adds the hook.
+#[derive(Clone, Default)]
+pub struct AsyncQueryStConfig {
+    hook: Option<AsyncHook>,
+}
+
+impl AsyncQueryStConfig {
+    /// Set the hook rewriting the coerced query value before deserialization
+    ///
+    /// The returned future can't borrow the request, clone what it needs from it.
+    pub fn hook<F>(mut self, f: F) -> Self
+    where
+        F: Fn(
+                serde_json::Value,
+                &HttpRequest,
+            ) -> BoxFuture<Result<serde_json::Value, QueryStPayloadError>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hook = Some(Arc::new(f));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Profile {
+        user: String,
+        id: u64,
+    }
+
+    #[actix_rt::test]
+    async fn test_async_hook() {
+        let config = AsyncQueryStConfig::default().hook(|mut value, req| {
+            let path = req.path().to_owned();
+            Box::pin(async move {
+                actix_rt::time::delay_for(Duration::from_millis(1)).await;
+                if value["id"] == "0" {
+                    return Err(QueryStPayloadError::UnknownKey("id".to_owned(), None));
+                }
+                value["user"] =
+                    format!("{}{}", path, value["user"].as_str().unwrap_or_default()).into();
+                Ok(value)
+            })
+        });
+
+        let req = TestRequest::with_uri("/users/?user=test&id=5")
+            .app_data(config.clone())
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = AsyncQuerySt::<Profile>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.user, "/users/test");
+        assert_eq!(s.id, 5);
+
+        let req = TestRequest::with_uri("/users/?user=test&id=0")
+            .app_data(config)
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(AsyncQuerySt::<Profile>::from_request(&req, &mut pl)
+            .await
+            .is_err());
+
+        let req = TestRequest::with_uri("/users/?user=test&id=5").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = AsyncQuerySt::<Profile>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.user, "test");
+    }
+
+    #[cfg(feature = "metrics")]
+    #[actix_rt::test]
+    async fn test_duration_excludes_hook() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let config = AsyncQueryStConfig::default().hook(|value, _| {
+            Box::pin(async move {
+                std::thread::sleep(Duration::from_millis(50));
+                Ok(value)
+            })
+        });
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        {
+            let _guard = metrics::set_default_local_recorder(&recorder);
+            let req = TestRequest::with_uri("/?user=test&id=5")
+                .app_data(config)
+                .to_srv_request();
+            let (req, mut pl) = req.into_parts();
+            assert!(AsyncQuerySt::<Profile>::from_request(&req, &mut pl)
+                .await
+                .is_ok());
+        }
+
+        let observations = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| match value {
+                DebugValue::Histogram(values)
+                    if key.key().name() == "querystring_extract_duration_seconds" =>
+                {
+                    Some(values)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(observations.len(), 1);
+        assert!(observations[0].0 < 0.05, "{}", observations[0].0);
+    }
+}
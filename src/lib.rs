@@ -1,17 +1,117 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 use std::future::{ready, Ready};
-use std::sync::Arc;
-use std::{fmt, ops};
+use std::time::{Duration, Instant};
 
-use actix_web::{dev::Payload, http::StatusCode, web::HttpRequest, FromRequest, ResponseError};
-use derive_more::{Display, From};
-use serde::de;
+use actix_web::{
+    dev::Payload,
+    http::{StatusCode, Uri},
+    web::HttpRequest,
+    FromRequest, ResponseError,
+};
+use derive_more::Display;
+use serde::{de, Serialize};
+
+/// Implement `into_inner`, `Deref`, `DerefMut` and a transparent `Debug` for an extractor
+/// wrapping its value in a single public field
+macro_rules! impl_wrapper {
+    ($name:ident<T $(, const $param:ident: $ty:ty)*>, $inner:ty) => {
+        impl<T $(, const $param: $ty)*> $name<T $(, $param)*> {
+            /// Deconstruct to the inner value
+            pub fn into_inner(self) -> $inner {
+                self.0
+            }
+        }
+
+        impl<T $(, const $param: $ty)*> std::ops::Deref for $name<T $(, $param)*> {
+            type Target = $inner;
+
+            fn deref(&self) -> &$inner {
+                &self.0
+            }
+        }
+
+        impl<T $(, const $param: $ty)*> std::ops::DerefMut for $name<T $(, $param)*> {
+            fn deref_mut(&mut self) -> &mut $inner {
+                &mut self.0
+            }
+        }
+
+        impl<T: std::fmt::Debug $(, const $param: $ty)*> std::fmt::Debug for $name<T $(, $param)*> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+mod bounded;
+mod coerced;
+mod config;
+mod default;
+mod fields;
+mod flat;
+mod form;
+mod helpers;
+mod hooked;
+mod lossy;
+mod normalize;
+mod optional;
+mod parsed;
+mod raw;
+mod streaming;
+mod stringify;
+#[cfg(feature = "validator")]
+mod validated;
+
+pub use bounded::BoundedQuerySt;
+pub use config::{
+    CaseMode, DuplicatePolicy, Notation, QueryStConfig, QueryStConfigBuilder, QueryStConfigError,
+    FORM_FALSY_TOKENS, FORM_TRUTHY_TOKENS,
+};
+pub use default::DefaultQuerySt;
+pub use form::{QueryStForm, QueryStFormConfig};
+#[cfg(feature = "humantime")]
+pub use helpers::deserialize_humantime_duration;
+#[cfg(feature = "chrono")]
+pub use helpers::deserialize_rfc3339;
+pub use helpers::{ci_enum, coerce_bool, coerce_number, coerce_opt};
+pub use hooked::{AsyncQuerySt, AsyncQueryStConfig, BoxFuture};
+pub use lossy::LossyQuerySt;
+pub use optional::OptionalQuerySt;
+pub use parsed::ParsedQuery;
+#[cfg(feature = "validator")]
+pub use validated::ValidatedQuerySt;
+
+use config::DEFAULT_CONFIG;
 
 /// Extract information from the request's query using `queryst`.
 ///
-/// **Note**: This extractor doesn't support anything beside strings as values ex: numbers
+/// **Note**: Values that look like numbers (ex: `64`, `-3.14`, `1e5`) are coerced into numbers
+/// for the fields that expect one, string fields keep the text as it was sent. Anything else
+/// is passed to the deserializer as a string unless enabled otherwise in
+/// [**QueryStConfig**](struct.QueryStConfig.html) (ex: `coerce_bools`).
 ///
 /// [**QueryStConfig**](struct.QueryStConfig.html) allows to configure extraction process.
 ///
+/// Repeated keys (ex: `tag=a&tag=b`) are collected into a list like with the bracket syntax
+/// (`tag[]=a&tag[]=b`), while a key given once stays a single value, so a list field needs
+/// at least two occurrences or the brackets. Lists of objects can be sent with
+/// `users[][name]=a&users[][age]=30&users[][name]=b`, a new object starting whenever a key
+/// repeats. Tuples and tuple structs (ex: `Point(f64, f64)`) are filled from lists
+/// positionally, `point[]=1&point[]=2`, and a list of another length is rejected.
+///
+/// Fields of a `#[serde(flatten)]` struct are read from the top level of the query, as if
+/// they were declared on the outer struct, so `page=2&per_page=10` fills a flattened
+/// `Pagination { page, per_page }` and the bracket syntax (`pagination[page]=2`) is not
+/// used for them. Nested non-flattened fields still use it (ex: `user[name]=x`). A flattened
+/// `HashMap<String, String>` catches the top-level keys no other field declares, numbers
+/// included, as long as they aren't nested. Next to a flattened struct with numeric fields
+/// it only catches the values that don't look like numbers. Since brackets nest, a
+/// `HashMap<String, serde_json::Value>` is needed to catch any key.
+///
 /// ## Example
 ///
 /// ```rust
@@ -43,61 +143,966 @@ use serde::de;
 ///        web::resource("/index.html").route(web::get().to(index)));
 /// }
 /// ```
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct QuerySt<T>(pub T);
 
+impl_wrapper!(QuerySt<T>, T);
+
 impl<T> QuerySt<T> {
-    /// Deconstruct to a inner value
-    pub fn into_inner(self) -> T {
-        self.0
+    /// Get a reference to the inner value, same as what `Deref` gives
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+
+    /// Get a mutable reference to the inner value, same as what `DerefMut` gives
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
     }
 
     /// Get query parameters from the path
+    ///
+    /// String values that fully match the JSON number grammar are converted into numbers
+    /// for the fields that expect one, string fields get them as sent. Ambiguous values like
+    /// `007` or `0x10` are left as strings.
+    ///
+    /// Keys missing from the query are left for serde to handle, so fields marked with
+    /// `#[serde(default)]` get their default value. Empty array elements (ex: `items[]=`)
+    /// are dropped, so `items[]=` alone results in an empty list. A single leading `?`
+    /// (ex: `?id=64`) is ignored, and so are empty pairs (ex: `a=1&&b=2`, a trailing `&` or
+    /// `=` alone), whatever the config.
     pub fn from_query(query_str: &str) -> Result<Self, QueryStPayloadError>
     where
         T: de::DeserializeOwned,
     {
-        let value = queryst::parse(query_str).map_err(QueryStPayloadError::DeserializeValue)?;
-        serde_json::from_value(value)
-            .map_err(QueryStPayloadError::DeserializeType)
+        Self::from_query_with(query_str, &DEFAULT_CONFIG)
+    }
+
+    /// Get query parameters from the query component of `uri`
+    ///
+    /// A URI without a query is handled like an empty query string.
+    pub fn from_uri(uri: &Uri) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        Self::from_query(uri.query().unwrap_or_default())
+    }
+
+    /// Get query parameters from the path, applying the value coercions enabled in `config`
+    ///
+    /// The error handler of `config` is not used, errors are returned as they are.
+    pub fn from_query_with(
+        query_str: &str,
+        config: &QueryStConfig,
+    ) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        parse_value_with(query_str, config)
+            .and_then(|value| Self::from_value_with(value, config))
+            .map_err(|e| e.with_query(query_str))
+    }
+
+    /// Same as [`from_query`](#method.from_query), along with the wall-clock time parsing and
+    /// deserialization took, ex: for profiling
+    pub fn from_query_timed(query_str: &str) -> (Result<Self, QueryStPayloadError>, Duration)
+    where
+        T: de::DeserializeOwned,
+    {
+        let start = Instant::now();
+        let result = Self::from_query(query_str);
+        (result, start.elapsed())
+    }
+
+    /// Same as [`from_query`](#method.from_query), along with a summary of the shape of the
+    /// parsed query, ex: for logging or adaptive limits
+    ///
+    /// ```rust
+    /// use actix_web_queryst::{QueryMeta, QuerySt};
+    ///
+    /// let (_, meta) =
+    ///     QuerySt::<serde_json::Value>::from_query_with_meta("a=1&b[c][]=2&b[c][]=3").unwrap();
+    /// assert_eq!(meta, QueryMeta { keys: 2, depth: 3, leaves: 3 });
+    /// ```
+    pub fn from_query_with_meta(query_str: &str) -> Result<(Self, QueryMeta), QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        recording_query(query_str, || {
+            let mut value = parse_value_with(query_str, &DEFAULT_CONFIG)?;
+            normalize_with(&mut value, &DEFAULT_CONFIG)?;
+            let meta = QueryMeta::of(&value);
+            Ok((Self::from_normalized(value, &DEFAULT_CONFIG)?, meta))
+        })
+    }
+
+    /// Get query parameters from the path, deserializing flat queries without building the
+    /// intermediate `serde_json::Value`
+    ///
+    /// Gives the same result as [`from_query`](#method.from_query) with fewer allocations.
+    /// Queries using the bracket syntax or repeated keys fall back to `from_query`, and so do
+    /// the failing queries where a number-like string was handed to a type that didn't say
+    /// what it expects, the way flattened fields and untagged enums do.
+    pub fn from_query_fast(query_str: &str) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        match flat::from_query(strip_question_mark(query_str)) {
+            Some(Ok(inner)) => Ok(QuerySt(inner)),
+            Some(Err(e)) if !e.guessed_number => {
+                Err(type_error(e.error, e.empty).with_query(query_str))
+            }
+            // Flattened fields and untagged enums may need the strings `from_query` retries with
+            Some(Err(_)) | None => Self::from_query(query_str),
+        }
+    }
+
+    /// Get query parameters from the path in a single pass over its pairs, without building
+    /// the intermediate `serde_json::Value`
+    ///
+    /// Gives the same result as [`from_query`](#method.from_query) while keeping strings
+    /// borrowed from the query when they need no decoding, which matters for very large
+    /// queries. Nested keys are supported with named brackets (ex: `filter[tags][]=a`),
+    /// queries using numeric indices, `[]` before other brackets or a key both as a value and
+    /// as a nested one fall back to `from_query`. So do the failing queries where a
+    /// number-like string was handed to a type that didn't say what it expects, the way
+    /// flattened fields and untagged enums do, since `from_query` retries them with strings.
+    pub fn from_query_streaming(query_str: &str) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        match streaming::from_query(strip_question_mark(query_str)) {
+            Some(Ok(inner)) => Ok(QuerySt(inner)),
+            Some(Err(e)) if !e.guessed_number => {
+                Err(type_error(e.error, e.empty).with_query(query_str))
+            }
+            // Flattened fields and untagged enums may need the strings `from_query` retries with
+            Some(Err(_)) | None => Self::from_query(query_str),
+        }
+    }
+
+    /// Deserialize a type borrowing from an already parsed query
+    ///
+    /// Unlike the other constructors this doesn't require `T: DeserializeOwned`, so fields
+    /// like `&'a str` can borrow from `parsed` instead of allocating. They can't borrow from
+    /// the query string itself since `queryst` decodes every value into a new string.
+    ///
+    /// `Cow<'a, str>` fields only borrow with `#[serde(borrow)]`, serde otherwise always
+    /// gives `Cow::Owned`, which is also what the other constructors give for
+    /// `Cow<'static, str>` fields.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use actix_web_queryst::{ParsedQuery, QuerySt};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Info<'a> {
+    ///     name: &'a str,
+    /// }
+    ///
+    /// let parsed = ParsedQuery::parse("name=test").unwrap();
+    /// let info = QuerySt::<Info>::from_query_borrowed(&parsed).unwrap();
+    /// assert_eq!(info.name, "test");
+    /// ```
+    pub fn from_query_borrowed<'a>(parsed: &'a ParsedQuery) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::Deserialize<'a>,
+    {
+        coerced::deserialize(parsed.value())
+            .map_err(QueryStPayloadError::from)
+            .map(QuerySt)
+    }
+
+    /// Get query parameters from the path, falling back to `defaults` for absent values
+    ///
+    /// The parsed query is deep-merged over `defaults`: nested objects are merged key by key
+    /// and any other value from the query replaces the default one. Defaults are used as they
+    /// are, without coercion.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use actix_web_queryst::QuerySt;
+    /// use serde::Deserialize;
+    /// use serde_json::json;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Pagination {
+    ///     page: u32,
+    ///     per_page: u32,
+    /// }
+    ///
+    /// let defaults = json!({"page": 1, "per_page": 20});
+    /// let p = QuerySt::<Pagination>::from_query_or("page=2", defaults).unwrap();
+    /// assert_eq!((p.page, p.per_page), (2, 20));
+    /// ```
+    pub fn from_query_or(
+        query_str: &str,
+        defaults: serde_json::Value,
+    ) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        recording_query(query_str, || {
+            let mut value = parse_value_with(query_str, &DEFAULT_CONFIG)?;
+            normalize_with(&mut value, &DEFAULT_CONFIG)?;
+            let mut merged = defaults;
+            normalize::merge_over(&mut merged, value);
+            Self::from_normalized(merged, &DEFAULT_CONFIG)
+        })
+    }
+
+    /// Get query parameters from the path along with the path parameters of the route
+    ///
+    /// Path parameters are added as top-level string values before coercion, so they
+    /// deserialize like query values. A path parameter takes precedence over a query
+    /// parameter of the same name, whatever the query holds for it.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    ///
+    /// use actix_web_queryst::QuerySt;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Listing {
+    ///     id: u64,
+    ///     sort: String,
+    /// }
+    ///
+    /// let mut path = HashMap::new();
+    /// path.insert("id".to_owned(), "5".to_owned());
+    /// let l = QuerySt::<Listing>::from_query_and_path("sort=asc", &path).unwrap();
+    /// assert_eq!((l.id, l.sort.as_str()), (5, "asc"));
+    /// ```
+    pub fn from_query_and_path(
+        query_str: &str,
+        path: &HashMap<String, String>,
+    ) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        recording_query(query_str, || {
+            let mut value = parse_value_with(query_str, &DEFAULT_CONFIG)?;
+            let path = path
+                .iter()
+                .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+                .collect::<serde_json::Map<_, _>>();
+            normalize::merge_over(&mut value, serde_json::Value::Object(path));
+            Self::from_value_with(value, &DEFAULT_CONFIG)
+        })
+    }
+
+    /// Get query parameters from the keys nested under `prefix` only
+    ///
+    /// `filter[status]=x&sort=y` with the `filter` prefix deserializes `T` from
+    /// `{"status": "x"}`, so several extractors can share a query over disjoint prefixes. A
+    /// missing prefix is handled like an empty query.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use actix_web_queryst::QuerySt;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Filter {
+    ///     status: String,
+    /// }
+    ///
+    /// let f = QuerySt::<Filter>::from_query_prefixed("filter[status]=open&sort=y", "filter").unwrap();
+    /// assert_eq!(f.status, "open");
+    /// ```
+    pub fn from_query_prefixed(query_str: &str, prefix: &str) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        recording_query(query_str, || {
+            let mut value = parse_value_with(query_str, &DEFAULT_CONFIG)?;
+            normalize_with(&mut value, &DEFAULT_CONFIG)?;
+            let nested = value
+                .get_mut(prefix)
+                .map(serde_json::Value::take)
+                .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
+            Self::from_normalized(nested, &DEFAULT_CONFIG)
+        })
+    }
+
+    /// Get query parameters from the path, reporting every malformed segment at once
+    ///
+    /// Each `&`-separated segment is checked on its own before the query is parsed. Besides
+    /// what `queryst` rejects, segments with malformed percent escapes, unbalanced brackets
+    /// in their key or an empty key are reported. If any segment is malformed, all of them
+    /// are returned in a `Multiple` error.
+    pub fn from_query_collect_errors(query_str: &str) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        let errors = raw::segment_errors(query_str);
+        if !errors.is_empty() {
+            return Err(QueryStPayloadError::Multiple(
+                errors,
+                Some(query_str.to_owned()),
+            ));
+        }
+        Self::from_query(query_str)
+    }
+
+    /// Get query parameters from the path along with the value parsed by `queryst`
+    ///
+    /// The returned value is the tree as `queryst` produced it, without the empty values pushed
+    /// with `[]=` and with the objects it pushed for each pair using `[]` merged, before any
+    /// coercion.
+    pub fn from_query_with_value(
+        query_str: &str,
+    ) -> Result<(Self, serde_json::Value), QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        recording_query(query_str, || {
+            let value = parse_value_with(query_str, &DEFAULT_CONFIG)?;
+            Self::from_value_with(value.clone(), &DEFAULT_CONFIG).map(|s| (s, value))
+        })
+    }
+
+    fn from_value_with(
+        mut value: serde_json::Value,
+        config: &QueryStConfig,
+    ) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        normalize_with(&mut value, config)?;
+        Self::from_normalized(value, config)
+    }
+
+    /// Deserialize a value that already went through `normalize_with`
+    fn from_normalized(
+        value: serde_json::Value,
+        config: &QueryStConfig,
+    ) -> Result<Self, QueryStPayloadError>
+    where
+        T: de::DeserializeOwned,
+    {
+        if let Some(required) = &config.required_keys {
+            let missing = required
+                .iter()
+                .filter(|key| value.get(key.as_str()).is_none())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                return Err(QueryStPayloadError::MissingRequired(missing, None));
+            }
+        }
+        if let (Some(allowed), Some(map)) = (&config.allowed_keys, value.as_object()) {
+            if let Some(key) = map.keys().find(|key| !allowed.contains(key.as_str())) {
+                return Err(QueryStPayloadError::ForbiddenKey(key.clone(), None));
+            }
+        }
+        if config.deny_unknown {
+            if let (Some(fields), Some(map)) = (fields::struct_fields::<T>(), value.as_object()) {
+                if let Some(key) = map.keys().find(|key| !fields.contains(&key.as_str())) {
+                    return Err(QueryStPayloadError::UnknownKey(key.clone(), None));
+                }
+            }
+        }
+        let empty = matches!(&value, serde_json::Value::Object(map) if map.is_empty());
+        coerced::deserialize(&value)
+            .map_err(|e| type_error(e, empty))
             .map(QuerySt)
     }
 }
 
-impl<T> ops::Deref for QuerySt<T> {
-    type Target = T;
+/// The error of deserializing a query, `empty` if it has no pairs
+fn type_error(
+    e: serde_path_to_error::Error<serde_json::Error>,
+    empty: bool,
+) -> QueryStPayloadError {
+    match QueryStPayloadError::DeserializeType(e, None) {
+        e if empty && e.kind() == QueryStErrorKind::MissingField => {
+            QueryStPayloadError::EmptyQuery(None)
+        }
+        e => e,
+    }
+}
 
-    fn deref(&self) -> &T {
-        &self.0
+/// Check the parsed query against the limits of `config` and apply its coercions
+fn normalize_with(
+    value: &mut serde_json::Value,
+    config: &QueryStConfig,
+) -> Result<(), QueryStPayloadError> {
+    let limits = normalize::Limits {
+        nodes: config.max_nodes,
+        depth: config.max_depth,
+        array_len: config.max_array_len,
+        value_bytes: config.max_value_bytes,
+    };
+    normalize::check_limits(value, &limits)?;
+    if config.bare_flags_as_true {
+        normalize::bare_flags_as_true(value);
+    }
+    if let Some(suffix) = &config.null_sentinel {
+        normalize::null_sentinels(value, suffix);
+    }
+    if let Some(aliases) = &config.aliases {
+        normalize::rename_keys(value, aliases);
+    }
+    if config.numeric_keys_to_array {
+        normalize::numeric_keys_to_array(value);
     }
+    if let Some(delimiter) = config.split_on {
+        normalize::split_on(value, delimiter);
+    }
+    // both can grow the value past the limits it was checked against
+    if config.numeric_keys_to_array || config.split_on.is_some() {
+        normalize::check_limits(value, &limits)?;
+    }
+    if config.trim_values {
+        normalize::trim_values(value);
+    }
+    if config.dedup_arrays {
+        normalize::dedup_arrays(value);
+    }
+    normalize::coerce_leaves(
+        value,
+        &normalize::Coercions {
+            empty_as_null: config.empty_as_none,
+            null_literal: config.null_literal,
+            case_modes: config.case_modes.as_ref(),
+            bools: Some(config.coerce_bool_digits).filter(|_| config.coerce_bools),
+            bool_tokens: config.bool_tokens.as_ref(),
+            radix_literals: config.radix_literals,
+        },
+    );
+    for transform in config.transforms.iter().flatten() {
+        transform(value);
+    }
+    Ok(())
 }
 
-impl<T> ops::DerefMut for QuerySt<T> {
-    fn deref_mut(&mut self) -> &mut T {
-        &mut self.0
+/// Run `f`, recording `query_str` as the query of its error
+pub(crate) fn recording_query<R>(
+    query_str: &str,
+    f: impl FnOnce() -> Result<R, QueryStPayloadError>,
+) -> Result<R, QueryStPayloadError> {
+    f().map_err(|e| e.with_query(query_str))
+}
+
+/// Check the raw query against the limits of `config` and parse it
+///
+/// A single leading `?` is ignored, so a query copied along with it parses the same.
+fn parse_value_with(
+    query_str: &str,
+    config: &QueryStConfig,
+) -> Result<serde_json::Value, QueryStPayloadError> {
+    let query_str = strip_question_mark(query_str);
+    check_raw(query_str, config)?;
+    parse_value(&rewrite_raw(query_str, config)?)
+}
+
+/// Same as `parse_value_with` for the request's query, reusing the value parsed by a previous
+/// extractor of the same request if it was given the same rewritten query
+///
+/// The cache is keyed on the query after `rewrite_raw`, so extractors with configs that
+/// rewrite it differently (ex: `duplicate_policy` or `case_insensitive_keys`) don't share a
+/// value.
+fn parse_req(
+    req: &HttpRequest,
+    config: &QueryStConfig,
+) -> Result<serde_json::Value, QueryStPayloadError> {
+    let query_str = strip_question_mark(req.query_string());
+    check_raw(query_str, config)?;
+    let rewritten = rewrite_raw(query_str, config)?;
+    if let Some(CachedQuery(entries)) = req.extensions().get::<CachedQuery>() {
+        if let Some((_, value)) = entries.iter().find(|(query, _)| *query == rewritten) {
+            return Ok(value.clone());
+        }
+    }
+    let value = parse_value(&rewritten)?;
+    let mut extensions = req.extensions_mut();
+    let entry = (rewritten.into_owned(), value.clone());
+    match extensions.get_mut::<CachedQuery>() {
+        Some(CachedQuery(entries)) => entries.push(entry),
+        None => extensions.insert(CachedQuery(vec![entry])),
     }
+    Ok(value)
 }
 
-impl<T: fmt::Debug> fmt::Debug for QuerySt<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+/// The query of a request as parsed by `queryst` for each rewritten form it was parsed in,
+/// stored in the request extensions
+struct CachedQuery(Vec<(String, serde_json::Value)>);
+
+/// Summary of an extraction, stored in the request extensions whether it succeeded or not
+///
+/// Every extractor of this crate inserts it, before the error handler runs, so a middleware
+/// can read it from `req.extensions()` to build logs or response headers. Keys are counted at
+/// the top level of the parsed query and are `0` when the query couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryStDiagnostics {
+    /// Length of the raw query string in bytes, or of the body for
+    /// [**QueryStForm**](struct.QueryStForm.html)
+    pub query_len: usize,
+    /// Number of top level keys of the parsed query
+    pub keys: usize,
+    /// Kind of the error the extraction failed with, if any
+    pub error: Option<QueryStErrorKind>,
+}
+
+impl QueryStDiagnostics {
+    /// Whether the extraction succeeded
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
     }
 }
 
-impl<T: fmt::Display> fmt::Display for QuerySt<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+/// An extraction in progress, recording its outcome the same way for every extractor
+///
+/// With the `tracing` feature, the `querystring.extract` span is created by `start` and
+/// entered by `run` around the parse and deserialize work, `keys` and `error` being recorded
+/// on it as they are known. With the `metrics` feature, only the time spent in `run` counts
+/// toward the recorded duration, so awaiting a hook or the body doesn't. `finish` inserts the `QueryStDiagnostics` of the request, and with
+/// the `tracing` and `metrics` features emits the events and updates the metrics of the
+/// outcome.
+pub(crate) struct Extraction {
+    extractor: &'static str,
+    query_len: usize,
+    keys: usize,
+    #[cfg(feature = "metrics")]
+    elapsed: Duration,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl Extraction {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn start(req: &HttpRequest, extractor: &'static str, query_len: usize) -> Self {
+        Extraction {
+            extractor,
+            query_len,
+            keys: 0,
+            #[cfg(feature = "metrics")]
+            elapsed: Duration::default(),
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "querystring.extract",
+                extractor,
+                path = req.path(),
+                keys = tracing::field::Empty,
+                error = tracing::field::Empty,
+            ),
+        }
+    }
+
+    /// Run `f` inside the span of the extraction, timing it
+    pub(crate) fn run<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        #[cfg(feature = "tracing")]
+        let span = self.span.clone();
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let result = f(self);
+        #[cfg(feature = "metrics")]
+        {
+            self.elapsed += start.elapsed();
+        }
+        result
+    }
+
+    /// Count the keys of the parsed query
+    pub(crate) fn parsed(&mut self, value: &serde_json::Value) {
+        self.keys = value.as_object().map_or(0, |map| map.len());
+        #[cfg(feature = "tracing")]
+        self.span.record("keys", self.keys);
+    }
+
+    pub(crate) fn finish<T>(self, req: &HttpRequest, result: &Result<T, QueryStPayloadError>) {
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("querystring_extract_duration_seconds")
+            .record(self.elapsed.as_secs_f64());
+        req.extensions_mut().insert(QueryStDiagnostics {
+            query_len: self.query_len,
+            keys: self.keys,
+            error: result.as_ref().err().map(QueryStPayloadError::kind),
+        });
+
+        #[cfg(feature = "tracing")]
+        {
+            let _enter = self.span.enter();
+            match result {
+                Ok(_) => tracing::debug!(keys = self.keys, "QuerySt extraction succeeded"),
+                Err(e) => {
+                    self.span.record("error", e.variant_name());
+                    tracing::warn!(
+                        error.variant = e.variant_name(),
+                        error.message = %e,
+                        "QuerySt extraction failed"
+                    )
+                }
+            }
+        }
+        #[cfg(feature = "metrics")]
+        match result {
+            Ok(_) => metrics::counter!("querystring_extract_success").increment(1),
+            Err(e) => metrics::counter!("querystring_extract_error", "variant" => e.variant_name())
+                .increment(1),
+        }
+        if let Err(e) = result {
+            log::debug!(
+                "Failed during {} extractor deserialization: {}. Request path: {:?}",
+                self.extractor,
+                e,
+                req.path()
+            );
+        }
     }
 }
 
-/// Extract information from the request's query using `queryst`.
+/// Shape of a parsed query, see
+/// [`QuerySt::from_query_with_meta`](struct.QuerySt.html#method.from_query_with_meta)
 ///
-/// **Note**: This extractor doesn't support anything beside strings as values ex: numbers
+/// Counted on the value handed to the deserializer, so empty array elements dropped
+/// beforehand are not counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryMeta {
+    /// Number of top level keys
+    pub keys: usize,
+    /// Nesting depth, counted like
+    /// [`QueryStConfig::max_depth`](struct.QueryStConfig.html#method.max_depth)
+    pub depth: usize,
+    /// Number of values that are neither arrays nor objects, nested ones included
+    pub leaves: usize,
+}
+
+impl QueryMeta {
+    fn of(value: &serde_json::Value) -> Self {
+        let mut meta = QueryMeta {
+            keys: value.as_object().map_or(0, |map| map.len()),
+            depth: 0,
+            leaves: 0,
+        };
+        let mut stack = vec![(value, 1)];
+        while let Some((value, depth)) = stack.pop() {
+            match value {
+                serde_json::Value::Array(items) => {
+                    meta.depth = meta.depth.max(depth);
+                    stack.extend(items.iter().map(|item| (item, depth + 1)));
+                }
+                serde_json::Value::Object(map) => {
+                    meta.depth = meta.depth.max(depth);
+                    stack.extend(map.values().map(|item| (item, depth + 1)));
+                }
+                _ => meta.leaves += 1,
+            }
+        }
+        meta
+    }
+}
+
+fn strip_question_mark(query_str: &str) -> &str {
+    query_str.strip_prefix('?').unwrap_or(query_str)
+}
+
+fn check_raw(query_str: &str, config: &QueryStConfig) -> Result<(), QueryStPayloadError> {
+    if let Some(limit) = config.max_length {
+        if query_str.len() > limit {
+            return Err(QueryStPayloadError::Overflow {
+                length: query_str.len(),
+                limit,
+                query: None,
+            });
+        }
+    }
+    if config.strict_percent_encoding {
+        if let Some(position) = raw::invalid_escape(query_str) {
+            return Err(QueryStPayloadError::InvalidEncoding(
+                format!("malformed percent escape at byte {}", position),
+                None,
+            ));
+        }
+    }
+    #[cfg(feature = "encoding_rs")]
+    let lossy_utf8 = config.lossy_utf8 || config.charset.is_some();
+    #[cfg(not(feature = "encoding_rs"))]
+    let lossy_utf8 = config.lossy_utf8;
+    if !lossy_utf8 {
+        if let Some(position) = raw::invalid_utf8(query_str) {
+            return Err(QueryStPayloadError::InvalidEncoding(
+                format!("invalid UTF-8 in the escape at byte {}", position),
+                None,
+            ));
+        }
+    }
+    if let Some(limit) = config.max_params {
+        let mut pairs = query_str
+            .split(|c| c == '&' || (c == ';' && config.allow_semicolons))
+            .filter(|pair| !pair.is_empty());
+        if pairs.nth(limit).is_some() {
+            return Err(QueryStPayloadError::TooManyParams { limit, query: None });
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite the raw query as `config` asks before handing it to `queryst`
+fn rewrite_raw<'a>(
+    query_str: &'a str,
+    config: &QueryStConfig,
+) -> Result<Cow<'a, str>, QueryStPayloadError> {
+    let query_str = if config.allow_semicolons && query_str.contains(';') {
+        Cow::Owned(query_str.replace(';', "&"))
+    } else {
+        Cow::Borrowed(query_str)
+    };
+    let query_str = match config.key_notation {
+        Some(Notation::Dot) => match raw::dots_to_brackets(&query_str) {
+            Cow::Owned(rewritten) => Cow::Owned(rewritten),
+            Cow::Borrowed(_) => query_str,
+        },
+        _ => query_str,
+    };
+    let query_str = match config.duplicate_policy {
+        Some(policy) => match raw::dedup_keys(&query_str, policy) {
+            Ok(Cow::Owned(deduped)) => Cow::Owned(deduped),
+            Ok(Cow::Borrowed(_)) => query_str,
+            Err(key) => return Err(QueryStPayloadError::DuplicateKey(key, None)),
+        },
+        None => query_str,
+    };
+    let query_str = if !config.plus_as_space && query_str.contains('+') {
+        Cow::Owned(query_str.replace('+', "%2B"))
+    } else {
+        query_str
+    };
+    #[cfg(feature = "encoding_rs")]
+    let query_str = match config.charset {
+        Some(encoding) => Cow::Owned(raw::transcode(&query_str, encoding)),
+        None => query_str,
+    };
+    if config.case_insensitive_keys {
+        if let Cow::Owned(lowercased) = raw::lowercase_keys(&query_str) {
+            return Ok(Cow::Owned(lowercased));
+        }
+    }
+    Ok(query_str)
+}
+
+/// Parse a query with `queryst`, dropping the empty values pushed with `[]=` and merging the
+/// objects it pushed for each pair using `[]`
+fn parse_value(query_str: &str) -> Result<serde_json::Value, QueryStPayloadError> {
+    let (mut value, syntax) = parse_tree(query_str)?;
+    normalize::drop_empty_array_items(&mut value, &syntax);
+    normalize::merge_array_objects(&mut value, &syntax);
+    Ok(value)
+}
+
+/// Parse a query exactly as `queryst` does, along with how its lists were given objects
+fn parse_tree(
+    query_str: &str,
+) -> Result<(serde_json::Value, raw::ArraySyntax), QueryStPayloadError> {
+    #[cfg(test)]
+    tests::PARSE_CALLS.with(|calls| calls.set(calls.get() + 1));
+    let syntax = raw::check_keys(query_str).map_err(QueryStPayloadError::from)?;
+    let value = queryst::parse(query_str).map_err(QueryStPayloadError::from)?;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(query_len = query_str.len(), "QuerySt query parsed");
+    Ok((value, syntax))
+}
+
+impl QuerySt<serde_json::Value> {
+    /// Parse a query string into a `serde_json::Value`, exactly as `queryst` produces it
+    ///
+    /// No coercion is applied, every leaf is a string (or `null` for keys without a value).
+    /// Use `QuerySt::<serde_json::Value>::from_query` to get the coerced value instead.
+    ///
+    /// ```rust
+    /// use actix_web_queryst::QuerySt;
+    /// use serde_json::json;
+    ///
+    /// let value = QuerySt::parse_raw("a[b]=1&c[]=2").unwrap();
+    /// assert_eq!(value, json!({"a": {"b": "1"}, "c": ["2"]}));
+    /// ```
+    pub fn parse_raw(query_str: &str) -> Result<serde_json::Value, QueryStPayloadError> {
+        parse_tree(query_str)
+            .map(|(value, _)| value)
+            .map_err(|e| e.with_query(query_str))
+    }
+
+    /// Parse a query string into compact JSON, after the default coercions
+    ///
+    /// Objects are sorted by key, which makes this a canonical form of the query, ex: for
+    /// access logs.
+    /// ```rust
+    /// use actix_web_queryst::QuerySt;
+    ///
+    /// let json = QuerySt::normalized_json("c[]=2&a[b]=1").unwrap();
+    /// assert_eq!(json, r#"{"a":{"b":1},"c":[2]}"#);
+    /// ```
+    pub fn normalized_json(query_str: &str) -> Result<String, QueryStPayloadError> {
+        recording_query(query_str, || {
+            let mut value = parse_value_with(query_str, &DEFAULT_CONFIG)?;
+            normalize_with(&mut value, &DEFAULT_CONFIG)?;
+            #[allow(unused_mut)]
+            let mut value: serde_json::Value =
+                coerced::deserialize(&value).map_err(QueryStPayloadError::from)?;
+            #[cfg(feature = "preserve_order")]
+            value.sort_all_objects();
+            Ok(value.to_string())
+        })
+    }
+
+    /// Deserialize the request's query into two types, parsing and coercing it only once
+    ///
+    /// The [**QueryStConfig**](struct.QueryStConfig.html) of the request is used like for the
+    /// extractor, but the error is returned as is instead of going through the error handler.
+    /// Both types see the whole query, so `deny_unknown` rejects the keys meant for the other.
+    ///
+    /// ```rust
+    /// use actix_web::{test::TestRequest, HttpRequest};
+    /// use actix_web_queryst::QuerySt;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Id {
+    ///     id: u64,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Page {
+    ///     page: u32,
+    /// }
+    ///
+    /// let req = TestRequest::with_uri("/?id=5&page=2").to_http_request();
+    /// let (id, page) = QuerySt::extract_pair::<Id, Page>(&req).unwrap();
+    /// assert_eq!((id.id, page.page), (5, 2));
+    /// ```
+    pub fn extract_pair<A, B>(req: &HttpRequest) -> Result<(A, B), QueryStPayloadError>
+    where
+        A: de::DeserializeOwned,
+        B: de::DeserializeOwned,
+    {
+        let config = QueryStConfig::from_req(req);
+        recording_query(req.query_string(), || {
+            let mut value = parse_req(req, config)?;
+            normalize_with(&mut value, config)?;
+            let QuerySt(a) = QuerySt::from_normalized(value.clone(), config)?;
+            let QuerySt(b) = QuerySt::from_normalized(value, config)?;
+            Ok((a, b))
+        })
+    }
+}
+
+impl<T> QuerySt<T>
+where
+    T: Serialize,
+{
+    /// Serialize the inner value back to JSON, ex: to echo the normalized query
+    ///
+    /// Fails only if `T`'s `Serialize` implementation does, or if it contains a map with
+    /// non-string keys.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use actix_web::{web, App, HttpResponse};
+    /// use actix_web_queryst::QuerySt;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Deserialize, Serialize)]
+    /// pub enum ResponseType {
+    ///    Token,
+    ///    Code
+    /// }
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// pub struct AuthRequest {
+    ///    id: u64,
+    ///    response_type: ResponseType,
+    /// }
+    ///
+    /// async fn reflect(info: QuerySt<AuthRequest>) -> HttpResponse {
+    ///     match info.to_json() {
+    ///         Ok(json) => HttpResponse::Ok().json(json),
+    ///         Err(_) => HttpResponse::InternalServerError().finish(),
+    ///     }
+    /// }
+    ///
+    /// let info = QuerySt::<AuthRequest>::from_query("id=64&response_type=Code").unwrap();
+    /// assert_eq!(
+    ///     info.to_json().unwrap(),
+    ///     serde_json::json!({"id": 64, "response_type": "Code"})
+    /// );
+    ///
+    /// let app = App::new().service(
+    ///     web::resource("/reflect").route(web::get().to(reflect)));
+    /// ```
+    pub fn to_json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(&self.0)
+    }
+
+    /// Serialize the inner value back into a query string, ex: for building links
+    ///
+    /// Nested values use the bracket syntax, so the result parses back into the same value
+    /// with [`from_query`](#method.from_query). Empty lists and maps are left out since a
+    /// query string can't express them, and the inner value must serialize as a map or
+    /// struct.
+    ///
+    /// ```rust
+    /// use actix_web_queryst::QuerySt;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     tags: Vec<String>,
+    /// }
+    ///
+    /// let search = QuerySt(Search { q: "rust web".to_owned(), tags: vec!["a".to_owned()] });
+    /// assert_eq!(search.try_into_query_string().unwrap(), "q=rust%20web&tags[]=a");
+    /// ```
+    pub fn try_into_query_string(&self) -> Result<String, serde_json::Error> {
+        stringify::to_query_string(&self.to_json()?)
+    }
+
+    /// Serialize the inner value into unencoded key/value pairs sorted by key, ex: as the
+    /// canonical form of a signed query
+    ///
+    /// Nested keys use the same bracket syntax as
+    /// [`try_into_query_string`](#method.try_into_query_string): `filter[name]` for objects,
+    /// `tags[]` for lists of plain values and `items[0][id]` for lists of objects or lists.
+    /// Items of a `tags[]` list keep their order, `null` gives an empty value and empty lists
+    /// and maps are left out.
+    ///
+    /// ```rust
+    /// use actix_web_queryst::QuerySt;
+    ///
+    /// let query = QuerySt::<serde_json::Value>::from_query("t=5&tags[]=b&tags[]=a&f[q]=x").unwrap();
+    /// assert_eq!(
+    ///     query.to_sorted_pairs().unwrap(),
+    ///     vec![
+    ///         ("f[q]".to_owned(), "x".to_owned()),
+    ///         ("t".to_owned(), "5".to_owned()),
+    ///         ("tags[]".to_owned(), "b".to_owned()),
+    ///         ("tags[]".to_owned(), "a".to_owned()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn to_sorted_pairs(&self) -> Result<Vec<(String, String)>, serde_json::Error> {
+        stringify::to_sorted_pairs(&self.to_json()?)
+    }
+}
+
+/// Parse a stored query string outside of the request flow, same as
+/// [`QuerySt::from_query`](struct.QuerySt.html#method.from_query).
 ///
 /// ## Example
 ///
 /// ```rust
-/// use actix_web::{web, App};
+/// use std::convert::TryFrom;
+///
 /// use actix_web_queryst::QuerySt;
 /// use serde::Deserialize;
 ///
@@ -113,130 +1118,625 @@ impl<T: fmt::Display> fmt::Display for QuerySt<T> {
 ///    response_type: ResponseType,
 /// }
 ///
-/// // Use `QuerySt` extractor for query information.
-/// // This handler get called only if request's query contains `id` field
-/// // The correct request for this handler would be `/index.html?id=64&response_type=Code"`
-/// async fn index(info: QuerySt<AuthRequest>) -> String {
-///     format!("Authorization request for client with id={} and type={:?}!", info.id, info.response_type)
-/// }
-///
-/// fn main() {
-///     let app = App::new().service(
-///        web::resource("/index.html")
-///            .route(web::get().to(index))); // <- use `Query` extractor
-/// }
+/// let info = QuerySt::<AuthRequest>::try_from("id=64&response_type=Code").unwrap();
+/// assert_eq!(info.id, 64);
 /// ```
-impl<T> FromRequest for QuerySt<T>
+impl<T> TryFrom<&str> for QuerySt<T>
 where
     T: de::DeserializeOwned,
 {
-    type Error = actix_web::Error;
-    type Future = Ready<Result<Self, actix_web::Error>>;
-    type Config = QueryStConfig;
+    type Error = QueryStPayloadError;
 
-    #[inline]
-    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let error_handler = req
-            .app_data::<Self::Config>()
-            .map(|c| c.ehandler.clone())
-            .unwrap_or(None);
-        let r = Self::from_query(req.query_string()).map_err(|e| {
-            log::debug!(
-                "Failed during QuerySt extractor deserialization. \
-                     Request path: {:?}",
-                req.path()
-            );
-            if let Some(error_handler) = error_handler {
-                (error_handler)(e, req)
-            } else {
-                e.into()
-            }
-        });
-        ready(r)
+    fn try_from(query_str: &str) -> Result<Self, Self::Error> {
+        Self::from_query(query_str)
     }
 }
 
-/// QuerySt extractor configuration
+/// Document the query parameters of `T` in `utoipa` OpenAPI specs
 ///
-/// **Note**: This extractor doesn't support anything beside strings as values ex: numbers
+/// Parameters are placed in the query unless `T` says otherwise. Nested fields can't be
+/// described with the bracket syntax in OpenAPI, they show up as a single parameter of the
+/// field's name, so flat structs document best.
+#[cfg(feature = "utoipa")]
+impl<T: utoipa::IntoParams> utoipa::IntoParams for QuerySt<T> {
+    fn into_params(
+        parameter_in_provider: impl Fn() -> Option<utoipa::openapi::path::ParameterIn>,
+    ) -> Vec<utoipa::openapi::path::Parameter> {
+        T::into_params(|| {
+            parameter_in_provider().or(Some(utoipa::openapi::path::ParameterIn::Query))
+        })
+    }
+}
+
+/// Convert into `actix_web::web::Query` for code written against it
 ///
-/// ## Example
+/// Only the extracted value is carried over, `web::Query` itself can't be extracted from a
+/// nested query since `serde_urlencoded` knows nothing of the bracket syntax.
+impl<T> From<QuerySt<T>> for actix_web::web::Query<T> {
+    fn from(query: QuerySt<T>) -> Self {
+        actix_web::web::Query(query.0)
+    }
+}
+
+/// Borrow the inner value, for generic code bounded on `AsRef`
 ///
 /// ```rust
-/// use actix_web::{error, web, App, FromRequest, HttpResponse};
-/// use actix_web_queryst::{QuerySt, QueryStConfig};
+/// use actix_web_queryst::QuerySt;
 /// use serde::Deserialize;
 ///
 /// #[derive(Deserialize)]
-/// struct Info {
-///     username: String,
+/// struct AuthRequest {
+///     id: u64,
 /// }
 ///
-/// /// deserialize `Info` from request's query
-/// async fn index(info: QuerySt<Info>) -> String {
-///     format!("Welcome {}!", info.username)
+/// fn takes(request: impl AsRef<AuthRequest>) -> u64 {
+///     request.as_ref().id
 /// }
 ///
-/// fn main() {
-///     let app = App::new().service(
-///         web::resource("/index.html").app_data(
-///             // change QuerySt extractor configuration
-///             QueryStConfig::default()
-///                 .error_handler(|err, req| {  // <- create custom error response
-///                     error::InternalError::from_response(
-///                         err, HttpResponse::Conflict().finish()).into()
-///                 })
-///             )
-///             .route(web::post().to(index))
-///     );
-/// }
+/// let qs = QuerySt::<AuthRequest>::from_query("id=64").unwrap();
+/// assert_eq!(takes(qs), 64);
 /// ```
-#[derive(Clone)]
-pub struct QueryStConfig {
-    ehandler:
-        Option<Arc<dyn Fn(QueryStPayloadError, &HttpRequest) -> actix_web::Error + Send + Sync>>,
+impl<T> AsRef<T> for QuerySt<T> {
+    fn as_ref(&self) -> &T {
+        &self.0
+    }
 }
 
-impl QueryStConfig {
-    /// Set custom error handler
-    pub fn error_handler<F>(mut self, f: F) -> Self
-    where
-        F: Fn(QueryStPayloadError, &HttpRequest) -> actix_web::Error + Send + Sync + 'static,
-    {
-        self.ehandler = Some(Arc::new(f));
-        self
+impl<T> AsMut<T> for QuerySt<T> {
+    fn as_mut(&mut self) -> &mut T {
+        &mut self.0
     }
 }
 
-impl Default for QueryStConfig {
-    fn default() -> Self {
-        QueryStConfig { ehandler: None }
+impl<T: fmt::Display> fmt::Display for QuerySt<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
     }
 }
 
-/// A set of errors that can occur during parsing query strings
-#[derive(Debug, Display, From)]
-pub enum QueryStPayloadError {
-    /// Error in deserialization to json values
-    #[display(fmt = "QuerySt invalid query provided: {:?}", _0)]
-    DeserializeValue(queryst::ParseError),
-
-    /// Error in deserialization from json values to the provided type
-    #[display(fmt = "QuerySt error in deserializing to type: {}", _0)]
-    DeserializeType(serde_json::Error),
+/// Serialize as the inner value, the wrapper is transparent
+impl<T: Serialize> Serialize for QuerySt<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
 }
 
-impl std::error::Error for QueryStPayloadError {}
-
-/// Return `BadRequest` for `QueryStPayloadError`
-impl ResponseError for QueryStPayloadError {
+impl<T> QuerySt<T>
+where
+    T: de::DeserializeOwned,
+{
+    /// Extract from the request's query right away, same as `from_request` without the
+    /// `Ready` future around the result
+    ///
+    /// The config of the request and its error handler are used like for the extractor. With
+    /// the `metrics` feature, the time spent parsing and deserializing is recorded in the
+    /// `querystring_extract_duration_seconds` histogram, whatever the outcome.
+    pub fn extract_sync(req: &HttpRequest) -> Result<Self, actix_web::Error> {
+        let config = QueryStConfig::from_req(req);
+        let mut extraction = Extraction::start(req, "QuerySt", req.query_string().len());
+        let result = extraction.run(|extraction| {
+            parse_req(req, config).and_then(|value| {
+                extraction.parsed(&value);
+                Self::from_value_with(value, config)
+            })
+        });
+        extraction.finish(req, &result);
+        result.map_err(|e| config.handle_error(e, req))
+    }
+}
+
+/// Extract information from the request's query using `queryst`.
+///
+/// **Note**: Values that look like numbers (ex: `64`, `-3.14`, `1e5`) are coerced into numbers
+/// for the fields that expect one, string fields keep the text as it was sent. Anything else
+/// is passed to the deserializer as a string unless enabled otherwise in
+/// [**QueryStConfig**](struct.QueryStConfig.html) (ex: `coerce_bools`).
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::QuerySt;
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// pub enum ResponseType {
+///    Token,
+///    Code
+/// }
+///
+/// #[derive(Deserialize)]
+/// pub struct AuthRequest {
+///    id: u64,
+///    response_type: ResponseType,
+/// }
+///
+/// // Use `QuerySt` extractor for query information.
+/// // This handler get called only if request's query contains `id` field
+/// // The correct request for this handler would be `/index.html?id=64&response_type=Code"`
+/// async fn index(info: QuerySt<AuthRequest>) -> String {
+///     format!("Authorization request for client with id={} and type={:?}!", info.id, info.response_type)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/index.html")
+///            .route(web::get().to(index))); // <- use `Query` extractor
+/// }
+/// ```
+impl<T> FromRequest for QuerySt<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, actix_web::Error>>;
+    type Config = QueryStConfig;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::extract_sync(req))
+    }
+}
+
+/// A set of errors that can occur during parsing query strings
+///
+/// Every variant ends with a snapshot of the query string that produced the error, see
+/// [`query`](#method.query), which isn't part of its `Display`.
+#[derive(Debug, Display)]
+pub enum QueryStPayloadError {
+    /// Error in deserialization to json values
+    #[display(
+        fmt = "QuerySt invalid query provided: {}",
+        "truncated(format!(\"{:?}\", _0))"
+    )]
+    DeserializeValue(queryst::ParseError, Option<String>),
+
+    /// Several segments of the query are malformed
+    #[display(
+        fmt = "QuerySt invalid query provided: {}",
+        "truncated(raw::join_errors(_0))"
+    )]
+    Multiple(Vec<queryst::ParseError>, Option<String>),
+
+    /// Error in deserialization from json values to the provided type, along with the path
+    /// of the value that failed (ex: `user.siblings[1]`)
+    ///
+    /// A single value given for a list is hinted with the bracket syntax (ex: `did you mean
+    /// `items[]=5`?`).
+    #[display(
+        fmt = "QuerySt error in deserializing to type: failed at `{}`: {}{}",
+        "truncated(_0.path().to_string())",
+        "truncated(_0.inner().to_string())",
+        "sequence_hint(_0).unwrap_or_default()"
+    )]
+    DeserializeType(
+        serde_path_to_error::Error<serde_json::Error>,
+        Option<String>,
+    ),
+
+    /// Query string has no parameters while the type has required fields
+    #[display(fmt = "QuerySt query string is required")]
+    EmptyQuery(Option<String>),
+
+    /// Query string is longer than the configured limit
+    #[display(
+        fmt = "QuerySt query length {} exceeds the limit of {} bytes",
+        length,
+        limit
+    )]
+    Overflow {
+        length: usize,
+        limit: usize,
+        query: Option<String>,
+    },
+
+    /// Parsed query is nested deeper than the configured limit
+    #[display(fmt = "QuerySt query nesting exceeds the limit of {} levels", limit)]
+    TooDeep { limit: usize, query: Option<String> },
+
+    /// Parsed query has more nodes than the configured budget
+    #[display(fmt = "QuerySt query exceeds the limit of {} parsed values", limit)]
+    TooManyNodes { limit: usize, query: Option<String> },
+
+    /// Parsed query has an array with more elements than the configured limit
+    #[display(fmt = "QuerySt query array exceeds the limit of {} elements", limit)]
+    ArrayTooLong { limit: usize, query: Option<String> },
+
+    /// Parsed query would be larger than the configured limit once serialized
+    #[display(fmt = "QuerySt parsed query exceeds the limit of {} bytes", limit)]
+    ValueTooLarge { limit: usize, query: Option<String> },
+
+    /// Query string has more parameters than the configured limit
+    #[display(fmt = "QuerySt query has more than {} parameters", limit)]
+    TooManyParams { limit: usize, query: Option<String> },
+
+    /// Query string is not properly encoded
+    #[display(fmt = "QuerySt invalid encoding in query: {}", "truncated(_0.clone())")]
+    InvalidEncoding(String, Option<String>),
+
+    /// Request body of a [**QueryStForm**](struct.QueryStForm.html) couldn't be read
+    #[display(fmt = "QuerySt error reading the request body: {}", _0)]
+    Payload(actix_web::error::PayloadError, Option<String>),
+
+    /// Query has a key the target type doesn't declare
+    #[display(fmt = "QuerySt unknown query parameter: {}", "truncated(_0.clone())")]
+    UnknownKey(String, Option<String>),
+
+    /// Query has a key outside the keys the config allows
+    #[display(fmt = "QuerySt forbidden query parameter: {}", "truncated(_0.clone())")]
+    ForbiddenKey(String, Option<String>),
+
+    /// Query lacks keys the config requires
+    #[display(
+        fmt = "QuerySt missing required query parameters: {}",
+        "_0.join(\", \")"
+    )]
+    MissingRequired(Vec<String>, Option<String>),
+
+    /// Query has a key more than once and the config rejects duplicates
+    #[display(fmt = "QuerySt duplicate query parameter: {}", "truncated(_0.clone())")]
+    DuplicateKey(String, Option<String>),
+
+    /// Deserialized value failed validation
+    #[cfg(feature = "validator")]
+    #[display(fmt = "QuerySt validation failed: {}", _0)]
+    Validation(validator::ValidationErrors, Option<String>),
+}
+
+/// The query snapshot of a `QueryStPayloadError`, by reference or mutably
+macro_rules! snapshot {
+    ($error:expr) => {
+        match $error {
+            QueryStPayloadError::DeserializeValue(_, query)
+            | QueryStPayloadError::Multiple(_, query)
+            | QueryStPayloadError::DeserializeType(_, query)
+            | QueryStPayloadError::EmptyQuery(query)
+            | QueryStPayloadError::Overflow { query, .. }
+            | QueryStPayloadError::TooDeep { query, .. }
+            | QueryStPayloadError::TooManyNodes { query, .. }
+            | QueryStPayloadError::ArrayTooLong { query, .. }
+            | QueryStPayloadError::ValueTooLarge { query, .. }
+            | QueryStPayloadError::TooManyParams { query, .. }
+            | QueryStPayloadError::InvalidEncoding(_, query)
+            | QueryStPayloadError::Payload(_, query)
+            | QueryStPayloadError::UnknownKey(_, query)
+            | QueryStPayloadError::ForbiddenKey(_, query)
+            | QueryStPayloadError::MissingRequired(_, query)
+            | QueryStPayloadError::DuplicateKey(_, query) => query,
+            #[cfg(feature = "validator")]
+            QueryStPayloadError::Validation(_, query) => query,
+        }
+    };
+}
+
+impl QueryStPayloadError {
+    /// What went wrong, without the details of the variant
+    ///
+    /// Type errors are split into `MissingField` and `TypeMismatch` by looking at the serde
+    /// message, since `serde_json` doesn't tell them apart otherwise.
+    pub fn kind(&self) -> QueryStErrorKind {
+        match self {
+            QueryStPayloadError::DeserializeValue(_, _)
+            | QueryStPayloadError::Multiple(_, _)
+            | QueryStPayloadError::InvalidEncoding(_, _)
+            | QueryStPayloadError::Payload(_, _) => QueryStErrorKind::MalformedQuery,
+            QueryStPayloadError::DeserializeType(e, _)
+                if e.inner().to_string().starts_with("missing field") =>
+            {
+                QueryStErrorKind::MissingField
+            }
+            QueryStPayloadError::DeserializeType(_, _) => QueryStErrorKind::TypeMismatch,
+            QueryStPayloadError::EmptyQuery(_) | QueryStPayloadError::MissingRequired(_, _) => {
+                QueryStErrorKind::MissingField
+            }
+            QueryStPayloadError::Overflow { .. }
+            | QueryStPayloadError::TooDeep { .. }
+            | QueryStPayloadError::TooManyNodes { .. }
+            | QueryStPayloadError::TooManyParams { .. }
+            | QueryStPayloadError::ArrayTooLong { .. }
+            | QueryStPayloadError::ValueTooLarge { .. } => QueryStErrorKind::TooLarge,
+            QueryStPayloadError::UnknownKey(_, _) | QueryStPayloadError::ForbiddenKey(_, _) => {
+                QueryStErrorKind::UnknownKey
+            }
+            QueryStPayloadError::DuplicateKey(_, _) => QueryStErrorKind::DuplicateKey,
+            #[cfg(feature = "validator")]
+            QueryStPayloadError::Validation(_, _) => QueryStErrorKind::Validation,
+        }
+    }
+
+    /// Details of the malformed parts of the query, empty unless this is a
+    /// `DeserializeValue`, `Multiple` or `InvalidEncoding` error
+    ///
+    /// A `Multiple` error gives one entry per malformed segment.
+    pub fn parse_errors(&self) -> Vec<ParseErrorInfo> {
+        match self {
+            QueryStPayloadError::DeserializeValue(e, _) => vec![ParseErrorInfo::from_queryst(e)],
+            QueryStPayloadError::Multiple(errors, _) => {
+                errors.iter().map(ParseErrorInfo::from_queryst).collect()
+            }
+            QueryStPayloadError::InvalidEncoding(message, _) => {
+                let mut info = ParseErrorInfo::from_reason(message);
+                if info.category == ParseErrorCategory::Other {
+                    info.category = ParseErrorCategory::Decoding;
+                }
+                vec![info]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The query string that produced this error
+    ///
+    /// Filled in by the `from_query` family and by the extractors, so error handlers and
+    /// callers alike can read it. For [**QueryStForm**](struct.QueryStForm.html) this is the
+    /// body, decoded as UTF-8. `None` for errors built by hand or returned by
+    /// [`QuerySt::from_query_borrowed`](struct.QuerySt.html#method.from_query_borrowed).
+    ///
+    /// ```rust
+    /// use actix_web::{error::InternalError, HttpResponse};
+    /// use actix_web_queryst::{QuerySt, QueryStConfig};
+    ///
+    /// let err = QuerySt::<u32>::from_query("id=x").unwrap_err();
+    /// assert_eq!(err.query(), Some("id=x"));
+    ///
+    /// let config = QueryStConfig::default().error_handler(|e, _| {
+    ///     let body = format!("{} in `{}`", e, e.query().unwrap_or_default());
+    ///     InternalError::from_response(e, HttpResponse::BadRequest().body(body)).into()
+    /// });
+    /// ```
+    pub fn query(&self) -> Option<&str> {
+        snapshot!(self).as_deref()
+    }
+
+    /// Record `query` as the query that produced this error, unless one is recorded already
+    pub(crate) fn with_query(mut self, query: &str) -> Self {
+        let snapshot = snapshot!(&mut self);
+        if snapshot.is_none() {
+            *snapshot = Some(query.to_owned());
+        }
+        self
+    }
+}
+
+impl From<queryst::ParseError> for QueryStPayloadError {
+    fn from(e: queryst::ParseError) -> Self {
+        QueryStPayloadError::DeserializeValue(e, None)
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for QueryStPayloadError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        QueryStPayloadError::DeserializeType(e, None)
+    }
+}
+
+#[cfg(feature = "validator")]
+impl From<validator::ValidationErrors> for QueryStPayloadError {
+    fn from(e: validator::ValidationErrors) -> Self {
+        QueryStPayloadError::Validation(e, None)
+    }
+}
+
+/// Longest part of the input, in chars, that an error message embeds
+///
+/// Error messages end up in logs, so a huge invalid query must not come out whole.
+const MAX_ERROR_VALUE_CHARS: usize = 256;
+
+/// Cut `s` to `MAX_ERROR_VALUE_CHARS` chars, marking the cut with an ellipsis
+fn truncated(mut s: String) -> String {
+    if let Some((end, _)) = s.char_indices().nth(MAX_ERROR_VALUE_CHARS) {
+        s.truncate(end);
+        s.push('…');
+    }
+    s
+}
+
+/// Hint at the bracket syntax when a single value was given where a list is expected
+///
+/// `serde_json` reports it as `invalid type: integer `5`, expected a sequence`, the value is
+/// taken back from the quotes of that message.
+fn sequence_hint(e: &serde_path_to_error::Error<serde_json::Error>) -> Option<String> {
+    use serde_path_to_error::Segment;
+
+    let message = e.inner().to_string();
+    let found = message
+        .strip_prefix("invalid type: ")?
+        .strip_suffix(", expected a sequence")?;
+    let value = match (found.find(['`', '"']), found.rfind(['`', '"'])) {
+        (Some(start), Some(end)) if start < end => &found[start + 1..end],
+        _ => "",
+    };
+
+    let mut key = String::new();
+    for segment in e.path().iter() {
+        match segment {
+            Segment::Map { key: name } if key.is_empty() => key.push_str(name),
+            Segment::Map { key: name } => key.push_str(&format!("[{}]", name)),
+            Segment::Seq { index } => key.push_str(&format!("[{}]", index)),
+            _ => return None,
+        }
+    }
+    if key.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "; expected array, did you mean `{}[]={}`?",
+        truncated(key),
+        truncated(value.to_owned())
+    ))
+}
+
+impl QueryStPayloadError {
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    fn variant_name(&self) -> &'static str {
+        match self {
+            QueryStPayloadError::DeserializeValue(_, _) => "DeserializeValue",
+            QueryStPayloadError::Multiple(_, _) => "Multiple",
+            QueryStPayloadError::DeserializeType(_, _) => "DeserializeType",
+            QueryStPayloadError::EmptyQuery(_) => "EmptyQuery",
+            QueryStPayloadError::MissingRequired(_, _) => "MissingRequired",
+            QueryStPayloadError::ForbiddenKey(_, _) => "ForbiddenKey",
+            QueryStPayloadError::Overflow { .. } => "Overflow",
+            QueryStPayloadError::TooDeep { .. } => "TooDeep",
+            QueryStPayloadError::TooManyNodes { .. } => "TooManyNodes",
+            QueryStPayloadError::TooManyParams { .. } => "TooManyParams",
+            QueryStPayloadError::ArrayTooLong { .. } => "ArrayTooLong",
+            QueryStPayloadError::ValueTooLarge { .. } => "ValueTooLarge",
+            QueryStPayloadError::InvalidEncoding(_, _) => "InvalidEncoding",
+            QueryStPayloadError::Payload(_, _) => "Payload",
+            QueryStPayloadError::UnknownKey(_, _) => "UnknownKey",
+            QueryStPayloadError::DuplicateKey(_, _) => "DuplicateKey",
+            #[cfg(feature = "validator")]
+            QueryStPayloadError::Validation(_, _) => "Validation",
+        }
+    }
+}
+
+/// The categories of [**QueryStPayloadError**](enum.QueryStPayloadError.html), see
+/// [`kind`](enum.QueryStPayloadError.html#method.kind)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QueryStErrorKind {
+    /// The query string itself is malformed
+    MalformedQuery,
+    /// A value doesn't fit the type of its field
+    TypeMismatch,
+    /// A required field is missing from the query
+    MissingField,
+    /// The query exceeds one of the configured limits
+    TooLarge,
+    /// The query has a key the target type doesn't declare
+    UnknownKey,
+    /// The query has a key more than once
+    DuplicateKey,
+    /// The deserialized value failed validation
+    #[cfg(feature = "validator")]
+    Validation,
+}
+
+impl QueryStErrorKind {
+    /// A stable name for the kind, ex: for machine-readable responses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryStErrorKind::MalformedQuery => "malformed_query",
+            QueryStErrorKind::TypeMismatch => "type_mismatch",
+            QueryStErrorKind::MissingField => "missing_field",
+            QueryStErrorKind::TooLarge => "too_large",
+            QueryStErrorKind::UnknownKey => "unknown_key",
+            QueryStErrorKind::DuplicateKey => "duplicate_key",
+            #[cfg(feature = "validator")]
+            QueryStErrorKind::Validation => "validation",
+        }
+    }
+}
+
+/// What was wrong with a malformed part of a query, see
+/// [`parse_errors`](enum.QueryStPayloadError.html#method.parse_errors)
+///
+/// `queryst` only reports a message, the other fields are recovered from it on a best-effort
+/// basis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErrorInfo {
+    /// What kind of problem this is
+    pub category: ParseErrorCategory,
+    /// The `&`-separated segment at fault, when known (ex: for `from_query_collect_errors`)
+    pub segment: Option<String>,
+    /// Byte offset of the problem in the segment, or in the query without segment
+    pub position: Option<usize>,
+    /// The message describing the problem
+    pub message: String,
+}
+
+/// Category of a [**ParseErrorInfo**](struct.ParseErrorInfo.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ParseErrorCategory {
+    /// The query couldn't be decoded (ex: with a `charset`)
+    Decoding,
+    /// A `%` isn't followed by two hex digits
+    MalformedEscape,
+    /// A segment has a value but no key
+    EmptyKey,
+    /// A key has a `[` without `]` or the other way around
+    UnbalancedBrackets,
+    /// Anything else `queryst` rejects
+    Other,
+}
+
+impl ParseErrorCategory {
+    /// A stable name for the category, ex: for machine-readable responses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseErrorCategory::Decoding => "decoding",
+            ParseErrorCategory::MalformedEscape => "malformed_escape",
+            ParseErrorCategory::EmptyKey => "empty_key",
+            ParseErrorCategory::UnbalancedBrackets => "unbalanced_brackets",
+            ParseErrorCategory::Other => "other",
+        }
+    }
+}
+
+impl ParseErrorInfo {
+    fn from_queryst(e: &queryst::ParseError) -> Self {
+        // segment errors of `raw::segment_errors` are formatted as "`segment`: reason"
+        let (segment, reason) = match e
+            .message
+            .strip_prefix('`')
+            .and_then(|rest| rest.rsplit_once("`: "))
+        {
+            Some((segment, reason)) => (Some(segment.to_owned()), reason),
+            None => (None, e.message.as_str()),
+        };
+        let mut info = ParseErrorInfo::from_reason(reason);
+        info.segment = segment;
+        if let queryst::ParseErrorKind::DecodingError = e.kind {
+            info.category = ParseErrorCategory::Decoding;
+        }
+        info
+    }
+
+    fn from_reason(reason: &str) -> Self {
+        let position = reason
+            .strip_prefix("malformed percent escape at byte ")
+            .and_then(|position| position.parse().ok());
+        let category = match reason {
+            _ if position.is_some() => ParseErrorCategory::MalformedEscape,
+            "empty key" => ParseErrorCategory::EmptyKey,
+            "unbalanced brackets in key" => ParseErrorCategory::UnbalancedBrackets,
+            _ => ParseErrorCategory::Other,
+        };
+        ParseErrorInfo {
+            category,
+            segment: None,
+            position,
+            message: reason.to_owned(),
+        }
+    }
+}
+
+impl std::error::Error for QueryStPayloadError {}
+
+/// Return `UnprocessableEntity` for `DeserializeType` (a well-formed query that doesn't fit the
+/// type), `PayloadTooLarge` for `Overflow` and `BadRequest` for the other errors
+///
+/// Use the error handler of [**QueryStConfig**](struct.QueryStConfig.html) to respond
+/// otherwise.
+impl ResponseError for QueryStPayloadError {
     fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+        match self {
+            QueryStPayloadError::DeserializeType(_, _) => StatusCode::UNPROCESSABLE_ENTITY,
+            QueryStPayloadError::Overflow { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::BAD_REQUEST,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
     use std::collections::HashMap;
 
     use actix_web::http::StatusCode;
@@ -248,6 +1748,10 @@ mod tests {
     use actix_web::test::TestRequest;
     use actix_web::HttpResponse;
 
+    thread_local! {
+        pub(crate) static PARSE_CALLS: Cell<usize> = const { Cell::new(0) };
+    }
+
     #[derive(Deserialize, Debug, Display)]
     struct Id {
         id: String,
@@ -274,6 +1778,7 @@ mod tests {
     }
 
     #[actix_rt::test]
+    #[allow(clippy::needless_borrow)]
     async fn test_service_request_extract() {
         let req = TestRequest::with_uri("/name/user1/").to_srv_request();
         assert!(QuerySt::<Id>::from_query(&req.query_string()).is_err());
@@ -289,6 +1794,16 @@ mod tests {
         assert_eq!(s.id, "test1");
     }
 
+    #[actix_rt::test]
+    async fn test_accessors() {
+        let mut s = QuerySt::<Id>::from_query("id=test").unwrap();
+        assert_eq!(s.get().id, "test");
+
+        s.get_mut().id = "test1".to_string();
+        assert!(std::ptr::eq(s.get(), &*s));
+        assert_eq!(s.into_inner().id, "test1");
+    }
+
     #[actix_rt::test]
     async fn test_request_extract() {
         let req = TestRequest::with_uri("/name/user1/").to_srv_request();
@@ -332,6 +1847,2001 @@ mod tests {
         assert_eq!(s.name, "test1");
     }
 
+    #[derive(Deserialize, Debug)]
+    struct Numbers {
+        id: u64,
+        ratio: f64,
+        sci: f64,
+        code: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_numeric_coercion() {
+        let s = QuerySt::<Numbers>::from_query("id=64&ratio=1.5&sci=2e3&code=007").unwrap();
+        assert_eq!(s.id, 64);
+        assert_eq!(s.ratio, 1.5);
+        assert_eq!(s.sci, 2000.0);
+        assert_eq!(s.code, "007");
+
+        let s = QuerySt::<Id>::from_query("id=0x10").unwrap();
+        assert_eq!(s.id, "0x10");
+    }
+
+    #[actix_rt::test]
+    async fn test_numeric_strings() {
+        for query in ["name=123", "name=1.5", "name=12345", "name=-1e3"] {
+            let value = &query[5..];
+            let s = QuerySt::<Name>::from_query(query).unwrap();
+            assert_eq!(s.name, value);
+            let s = QuerySt::<Name>::from_query_fast(query).unwrap();
+            assert_eq!(s.name, value);
+            let s = QuerySt::<Name>::from_query_streaming(query).unwrap();
+            assert_eq!(s.name, value);
+        }
+
+        let s = QuerySt::<User>::from_query("name=1&sib[]=2&abblities[x]=3").unwrap();
+        assert_eq!((s.name.as_str(), s.siblings[0].as_str()), ("1", "2"));
+        assert_eq!(s.abblities["x"], "3");
+    }
+
+    #[actix_rt::test]
+    async fn test_newtypes() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct UserId(u64);
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Tag(String);
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Ratio(f64);
+
+        #[derive(Deserialize, Debug)]
+        struct Lookup {
+            id: UserId,
+            #[serde(default)]
+            tags: Vec<Tag>,
+            #[serde(default)]
+            friends: Vec<UserId>,
+            ratio: Option<Ratio>,
+        }
+
+        let s = QuerySt::<Lookup>::from_query("id=5").unwrap();
+        assert_eq!(s.id, UserId(5));
+        assert!(s.tags.is_empty());
+        assert_eq!(s.ratio, None);
+
+        let s = QuerySt::<Lookup>::from_query(
+            "id=5&tags[]=a&tags[]=b&friends[]=1&friends[]=2&ratio=0.5",
+        )
+        .unwrap();
+        assert_eq!(s.tags, vec![Tag("a".to_owned()), Tag("b".to_owned())]);
+        assert_eq!(s.friends, vec![UserId(1), UserId(2)]);
+        assert_eq!(s.ratio, Some(Ratio(0.5)));
+
+        let s = QuerySt::<Lookup>::from_query_fast("id=5&ratio=2").unwrap();
+        assert_eq!(s.id, UserId(5));
+        assert_eq!(s.ratio, Some(Ratio(2.0)));
+        let s = QuerySt::<Lookup>::from_query_streaming("id=5&tags[]=a&friends[]=7").unwrap();
+        assert_eq!(s.tags, vec![Tag("a".to_owned())]);
+        assert_eq!(s.friends, vec![UserId(7)]);
+        assert!(QuerySt::<Lookup>::from_query("id=a").is_err());
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Flags {
+        active: bool,
+        name: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_bool_coercion() {
+        assert!(QuerySt::<Flags>::from_query("active=true&name=false").is_err());
+
+        let config = QueryStConfig::default().coerce_bools(true);
+        let s = QuerySt::<Flags>::from_query_with("active=true&name=yes", &config).unwrap();
+        assert!(s.active);
+        assert_eq!(s.name, "yes");
+        assert!(QuerySt::<Flags>::from_query_with("active=1&name=yes", &config).is_err());
+
+        let config = config.coerce_bool_digits(true);
+        let s = QuerySt::<Flags>::from_query_with("active=0&name=yes", &config).unwrap();
+        assert!(!s.active);
+
+        let req = TestRequest::with_uri("/?active=false&name=test")
+            .app_data(QueryStConfig::default().coerce_bools(true))
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = QuerySt::<Flags>::from_request(&req, &mut pl).await.unwrap();
+        assert!(!s.active);
+    }
+
+    #[actix_rt::test]
+    async fn test_bool_tokens() {
+        #[derive(Deserialize, Debug)]
+        struct Signup {
+            subscribe: bool,
+            #[serde(default)]
+            terms: bool,
+        }
+
+        let config = QueryStConfig::default().coerce_bools(true);
+        assert!(QuerySt::<Signup>::from_query_with("subscribe=on", &config).is_err());
+
+        let config = config.bool_tokens(FORM_TRUTHY_TOKENS, FORM_FALSY_TOKENS);
+        let s = QuerySt::<Signup>::from_query_with("subscribe=on", &config).unwrap();
+        assert!(s.subscribe);
+        let s = QuerySt::<Signup>::from_query_with("subscribe=OFF&terms=Yes", &config).unwrap();
+        assert!(!s.subscribe);
+        assert!(s.terms);
+
+        let config = config.bool_tokens(&["y"], &["n"]);
+        let s = QuerySt::<Signup>::from_query_with("subscribe=Y&terms=n", &config).unwrap();
+        assert!(s.subscribe);
+        assert!(QuerySt::<Signup>::from_query_with("subscribe=true", &config).is_err());
+
+        assert_eq!(
+            QueryStConfig::builder()
+                .bool_tokens(FORM_TRUTHY_TOKENS, FORM_FALSY_TOKENS)
+                .build()
+                .err(),
+            Some(QueryStConfigError::BoolTokensWithoutBools)
+        );
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Optionals {
+        name: Option<String>,
+        age: Option<u32>,
+    }
+
+    #[actix_rt::test]
+    async fn test_empty_as_none() {
+        let s = QuerySt::<Optionals>::from_query("name=").unwrap();
+        assert_eq!(s.name.as_deref(), Some(""));
+        assert!(QuerySt::<Optionals>::from_query("age=").is_err());
+
+        let config = QueryStConfig::default().empty_as_none(true);
+        let s = QuerySt::<Optionals>::from_query_with("name=&age=", &config).unwrap();
+        assert_eq!(s.name, None);
+        assert_eq!(s.age, None);
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_with_value() {
+        let (s, value) =
+            QuerySt::<User>::from_query_with_value("name=test&sib[]=a&sib[]=b&abblities[x]=y")
+                .unwrap();
+        assert_eq!(s.siblings, vec!["a", "b"]);
+        assert_eq!(value["sib"], serde_json::json!(["a", "b"]));
+
+        let (s, value) =
+            QuerySt::<Numbers>::from_query_with_value("id=64&ratio=1.5&sci=2e3&code=007").unwrap();
+        assert_eq!(s.id, 64);
+        assert_eq!(value["id"], serde_json::json!("64"));
+    }
+
+    #[actix_rt::test]
+    async fn test_max_length() {
+        let req = TestRequest::with_uri("/?id=0123456789")
+            .app_data(QueryStConfig::default().max_length(8))
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let err = QuerySt::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let config = QueryStConfig::default().max_length(8);
+        assert!(QuerySt::<Id>::from_query_with("id=test", &config).is_ok());
+        match QuerySt::<Id>::from_query_with("id=test1234", &config) {
+            Err(QueryStPayloadError::Overflow { length, limit, .. }) => {
+                assert_eq!((length, limit), (11, 8))
+            }
+            _ => panic!("expected an overflow error"),
+        }
+
+        // checked before the query is scanned for bad escapes
+        let config = config.lossy_utf8(false).strict_percent_encoding(true);
+        let err = QuerySt::<Id>::from_query_with("id=%FF%g1", &config).unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::Overflow { limit: 8, .. }
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_max_depth() {
+        let config = QueryStConfig::default().max_depth(4);
+        let deep = format!("a{}=x", "[b]".repeat(3));
+        assert!(QuerySt::<serde_json::Value>::from_query_with(&deep, &config).is_ok());
+
+        let deep = format!("a{}=x", "[b]".repeat(4));
+        let err = QuerySt::<serde_json::Value>::from_query_with(&deep, &config).unwrap_err();
+        assert!(matches!(err, QueryStPayloadError::TooDeep { limit: 4, .. }));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_max_nodes() {
+        #[derive(Deserialize, Debug)]
+        struct Filter {
+            active: Option<bool>,
+            ids: Vec<u32>,
+            name: Option<String>,
+        }
+
+        let config = QueryStConfig::default()
+            .max_nodes(5)
+            .max_depth(2)
+            .trim_values(true)
+            .null_literal(true)
+            .coerce_bools(true);
+        let s =
+            QuerySt::<Filter>::from_query_with("active=true&ids[]=+1&name=null", &config).unwrap();
+        assert_eq!(s.active, Some(true));
+        assert_eq!(s.ids, vec![1]);
+        assert_eq!(s.name, None);
+
+        let err = QuerySt::<Filter>::from_query_with("active=true&ids[]=1&ids[]=2&name=x", &config)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::TooManyNodes { limit: 5, .. }
+        ));
+        assert_eq!(err.kind(), QueryStErrorKind::TooLarge);
+        let err = QuerySt::<Filter>::from_query_with("active[x][]=1", &config).unwrap_err();
+        assert!(matches!(err, QueryStPayloadError::TooDeep { limit: 2, .. }));
+
+        let config = QueryStConfig::default().max_nodes(5).split_on(',');
+        let err = QuerySt::<Ids>::from_query_with("ids=1,2,3,4,5,6,7,8,9,10", &config).unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::TooManyNodes { limit: 5, .. }
+        ));
+        assert!(QuerySt::<Filter>::from_query("ids[]=1&ids[]=2&ids[]=3&name=x").is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_max_array_len() {
+        let config = QueryStConfig::default().max_array_len(100);
+        let query = (0..1000)
+            .map(|i| format!("ids[]={}", i))
+            .collect::<Vec<_>>()
+            .join("&");
+        let err = QuerySt::<Ids>::from_query_with(&query, &config).unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::ArrayTooLong { limit: 100, .. }
+        ));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        let s = QuerySt::<Family>::from_query_with("user[ids][]=1&user[ids][]=2", &config);
+        assert_eq!(s.unwrap().user.ids, vec![1, 2]);
+        let s = QuerySt::<Listing>::from_query_with("items[]=&items[]=&items[]=a", &config);
+        assert_eq!(s.unwrap().items, vec!["a"]);
+
+        let config = QueryStConfig::default().max_array_len(3).split_on(',');
+        let err = QuerySt::<Ids>::from_query_with("ids=1,2,3,4,5,6,7,8,9,10", &config).unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::ArrayTooLong { limit: 3, .. }
+        ));
+        let s = QuerySt::<Ids>::from_query_with("ids=1,2,3", &config).unwrap();
+        assert_eq!(s.ids, vec![1, 2, 3]);
+        let config = QueryStConfig::default().max_array_len(1);
+        assert!(
+            QuerySt::<Family>::from_query_with("user[ids][]=1&user[ids][]=2", &config).is_err()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_max_params() {
+        let config = QueryStConfig::default().max_params(100);
+        let query = (0..1000)
+            .map(|i| format!("k{}=v", i))
+            .collect::<Vec<_>>()
+            .join("&");
+        let err = QuerySt::<serde_json::Value>::from_query_with(&query, &config).unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::TooManyParams { limit: 100, .. }
+        ));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        let config = QueryStConfig::default().max_params(2);
+        assert!(QuerySt::<serde_json::Value>::from_query_with("a=1&&b=2&", &config).is_ok());
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Listing {
+        #[serde(default)]
+        page: u32,
+        #[serde(default)]
+        items: Vec<String>,
+    }
+
+    #[actix_rt::test]
+    async fn test_serde_defaults() {
+        let s = QuerySt::<Listing>::from_query("").unwrap();
+        assert_eq!(s.page, 0);
+        assert!(s.items.is_empty());
+
+        let s = QuerySt::<Listing>::from_query("page=2&items[]=").unwrap();
+        assert_eq!(s.page, 2);
+        assert!(s.items.is_empty());
+
+        let s = QuerySt::<Listing>::from_query("items[]=&items[]=a").unwrap();
+        assert_eq!(s.items, vec!["a"]);
+
+        for query in ["items=a&items=", "items=&items=a"] {
+            let s = QuerySt::<Listing>::from_query(query).unwrap();
+            assert_eq!(s.items.len(), 2, "{}", query);
+            let s = QuerySt::<Listing>::from_query_streaming(query).unwrap();
+            assert_eq!(s.items.len(), 2, "{}", query);
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Name {
+        name: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_parse_cached_per_request() {
+        let req = TestRequest::with_uri("/?id=test&name=user").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+
+        let before = PARSE_CALLS.with(Cell::get);
+        let id = QuerySt::<Id>::from_request(&req, &mut pl).await.unwrap();
+        let name = QuerySt::<Name>::from_request(&req, &mut pl).await.unwrap();
+        assert_eq!(PARSE_CALLS.with(Cell::get) - before, 1);
+        assert_eq!(id.id, "test");
+        assert_eq!(name.name, "user");
+    }
+
+    #[test]
+    fn test_parse_cached_per_config() {
+        let req = TestRequest::with_uri("/?ID=1&id=2").to_http_request();
+        let case_insensitive = QueryStConfig::default().case_insensitive_keys(true);
+
+        let before = PARSE_CALLS.with(Cell::get);
+        let value = parse_req(&req, &DEFAULT_CONFIG).unwrap();
+        assert_eq!(value, serde_json::json!({"ID": "1", "id": "2"}));
+        let value = parse_req(&req, &case_insensitive).unwrap();
+        assert_eq!(value, serde_json::json!({"id": "2"}));
+        assert_eq!(PARSE_CALLS.with(Cell::get) - before, 2);
+
+        // both forms stay cached
+        parse_req(&req, &DEFAULT_CONFIG).unwrap();
+        parse_req(&req, &case_insensitive).unwrap();
+        assert_eq!(PARSE_CALLS.with(Cell::get) - before, 2);
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Pagination {
+        page: u32,
+        per_page: Option<u32>,
+    }
+
+    #[test]
+    fn test_extract_pair() {
+        let req = TestRequest::with_uri("/?id=test&page=2&per_page=10").to_http_request();
+        let before = PARSE_CALLS.with(Cell::get);
+        let (id, pagination) = QuerySt::extract_pair::<Id, Pagination>(&req).unwrap();
+        assert_eq!(PARSE_CALLS.with(Cell::get) - before, 1);
+        assert_eq!(id.id, "test");
+        assert_eq!(pagination.page, 2);
+        assert_eq!(pagination.per_page, Some(10));
+
+        let req = TestRequest::with_uri("/?id=test").to_http_request();
+        let err = QuerySt::extract_pair::<Id, Pagination>(&req).unwrap_err();
+        assert_eq!(err.kind(), QueryStErrorKind::MissingField);
+
+        let req = TestRequest::with_uri("/?id=test&page=2")
+            .app_data(QueryStConfig::default().deny_unknown(true))
+            .to_http_request();
+        let err = QuerySt::extract_pair::<Id, Pagination>(&req).unwrap_err();
+        assert_eq!(err.kind(), QueryStErrorKind::UnknownKey);
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_fast() {
+        let s = QuerySt::<Numbers>::from_query_fast("id=64&ratio=1.5&sci=2e3&code=007").unwrap();
+        assert_eq!((s.id, s.code.as_str()), (64, "007"));
+
+        let s = QuerySt::<User>::from_query_fast("name=test&sib[]=a&abblities[x]=y").unwrap();
+        assert_eq!(s.siblings, vec!["a"]);
+
+        // a failing query is reported without parsing it again
+        let before = PARSE_CALLS.with(Cell::get);
+        let err = QuerySt::<Numbers>::from_query_fast("id=x").unwrap_err();
+        assert_eq!(PARSE_CALLS.with(Cell::get) - before, 0);
+        assert_eq!(err.query(), Some("id=x"));
+        let standard = QuerySt::<Numbers>::from_query("id=x").unwrap_err();
+        assert_eq!(err.kind(), standard.kind());
+        assert_eq!(err.to_string(), standard.to_string());
+        let err = QuerySt::<Id>::from_query_fast("").unwrap_err();
+        assert!(matches!(err, QueryStPayloadError::EmptyQuery(_)));
+
+        // flattened fields get the number-like strings back from `from_query`
+        #[derive(Deserialize, Debug)]
+        struct Wrapper {
+            #[serde(flatten)]
+            name: Name,
+        }
+        let s = QuerySt::<Wrapper>::from_query_fast("name=12").unwrap();
+        assert_eq!(s.name.name, "12");
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[actix_rt::test]
+    async fn test_preserve_order() {
+        #[derive(Deserialize, Debug)]
+        struct Echo {
+            params: indexmap::IndexMap<String, String>,
+        }
+
+        let query = "params[zeta]=a&params[alpha]=b&params[mu]=c";
+        for s in [
+            QuerySt::<Echo>::from_query(query).unwrap(),
+            QuerySt::<Echo>::from_query_streaming(query).unwrap(),
+        ] {
+            let keys = s.params.keys().map(String::as_str).collect::<Vec<_>>();
+            assert_eq!(keys, vec!["zeta", "alpha", "mu"]);
+        }
+
+        let s = QuerySt::<indexmap::IndexMap<String, String>>::from_query_fast("b=x&a=y").unwrap();
+        assert_eq!(s.keys().collect::<Vec<_>>(), vec!["b", "a"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_streaming() {
+        for query in &[
+            "?name=test&sib[]=a&sib[]=b&abblities[x]=y&abblities[z]=w",
+            "name=a+b&sib=c&sib=d&abblities[x%20y]=%2526",
+            "name=test&sib[0]=a&sib[1]=b&abblities[x]=y",
+            "name=test&sib[]=a",
+        ] {
+            let fields = |s: QuerySt<User>| (s.0.name, s.0.siblings, s.0.abblities);
+            let streaming = QuerySt::<User>::from_query_streaming(query)
+                .map(fields)
+                .map_err(|e| e.to_string());
+            let standard = QuerySt::<User>::from_query(query)
+                .map(fields)
+                .map_err(|e| e.to_string());
+            assert_eq!(streaming, standard, "{}", query);
+        }
+
+        let s =
+            QuerySt::<Members>::from_query_streaming("users[][name]=a&users[][age]=30").unwrap();
+        assert_eq!(s.users[0].age, 30);
+
+        // a failing query is reported without parsing it again
+        let before = PARSE_CALLS.with(Cell::get);
+        let err = QuerySt::<Id>::from_query_streaming("name=x").unwrap_err();
+        assert_eq!(PARSE_CALLS.with(Cell::get) - before, 0);
+        assert_eq!(err.kind(), QueryStErrorKind::MissingField);
+        assert_eq!(err.query(), Some("name=x"));
+        let standard = QuerySt::<Id>::from_query("name=x").unwrap_err();
+        assert_eq!(err.to_string(), standard.to_string());
+        let err = QuerySt::<Id>::from_query_streaming("").unwrap_err();
+        assert!(matches!(err, QueryStPayloadError::EmptyQuery(_)));
+
+        // untagged enums get the number-like strings back from `from_query`
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(untagged)]
+        enum Key {
+            Named { key: String },
+        }
+        let s = QuerySt::<Key>::from_query_streaming("key=12").unwrap();
+        assert_eq!(
+            s.0,
+            Key::Named {
+                key: "12".to_owned()
+            }
+        );
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Ids {
+        ids: Vec<u32>,
+    }
+
+    #[actix_rt::test]
+    async fn test_split_on() {
+        assert!(QuerySt::<Ids>::from_query("ids=1,2,3").is_err());
+
+        let config = QueryStConfig::default().split_on(',');
+        let s = QuerySt::<Ids>::from_query_with("ids=1,2,3", &config).unwrap();
+        assert_eq!(s.ids, vec![1, 2, 3]);
+        let s = QuerySt::<Ids>::from_query_with("ids[]=1&ids[]=2,3", &config).unwrap();
+        assert_eq!(s.ids, vec![1, 2, 3]);
+
+        let s = QuerySt::<Listing>::from_query_with("items=a,,b", &config).unwrap();
+        assert_eq!(s.items, vec!["a", "", "b"]);
+        let config = config.empty_as_none(true);
+        let s = QuerySt::<serde_json::Value>::from_query_with("items=a,,b", &config).unwrap();
+        assert_eq!(s.0, serde_json::json!({"items": ["a", null, "b"]}));
+    }
+
+    #[actix_rt::test]
+    async fn test_case_insensitive_keys() {
+        assert!(QuerySt::<Id>::from_query("ID=64").is_err());
+
+        let config = QueryStConfig::default().case_insensitive_keys(true);
+        let s = QuerySt::<Numbers>::from_query_with("ID=64&Ratio=1&SCI=2&code=x", &config).unwrap();
+        assert_eq!(s.id, 64);
+        for (query, id) in [("id=1&ID=2", 2), ("ID=1&id=2", 2), ("id=2&ID=1", 1)] {
+            let query = format!("{}&ratio=1&sci=2&code=x", query);
+            let s = QuerySt::<Numbers>::from_query_with(&query, &config).unwrap();
+            assert_eq!(s.id, id, "{}", query);
+        }
+
+        let s = QuerySt::<serde_json::Value>::from_query_with("User[Name]=a&user[age]=1", &config)
+            .unwrap();
+        assert_eq!(s.0, serde_json::json!({"user": {"name": "a", "age": 1}}));
+    }
+
+    #[actix_rt::test]
+    async fn test_deny_unknown() {
+        assert!(QuerySt::<Id>::from_query("id=x&foo=bar").is_ok());
+
+        let config = QueryStConfig::default().deny_unknown(true);
+        assert!(QuerySt::<Id>::from_query_with("id=x", &config).is_ok());
+        let err = QuerySt::<Id>::from_query_with("id=x&foo=bar", &config).unwrap_err();
+        assert!(matches!(&err, QueryStPayloadError::UnknownKey(key, _) if key == "foo"));
+        assert_eq!(err.to_string(), "QuerySt unknown query parameter: foo");
+
+        // renamed fields are matched by their serialized name
+        let s = QuerySt::<User>::from_query_with("name=a&sib[]=b&abblities[c]=d", &config);
+        assert!(s.is_ok());
+        let s = QuerySt::<serde_json::Value>::from_query_with("foo=bar", &config);
+        assert!(s.is_ok());
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Family {
+        user: Ids,
+    }
+
+    #[actix_rt::test]
+    async fn test_deserialize_type_path() {
+        let err = QuerySt::<Family>::from_query("user[ids][]=1&user[ids][]=x").unwrap_err();
+        match &err {
+            QueryStPayloadError::DeserializeType(e, _) => {
+                assert_eq!(e.path().to_string(), "user.ids[1]")
+            }
+            _ => panic!("expected a type error"),
+        }
+        assert!(
+            err.to_string().contains("failed at `user.ids[1]`"),
+            "{}",
+            err
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_from_uri() {
+        let uri = "/p?id=test".parse::<Uri>().unwrap();
+        assert_eq!(QuerySt::<Id>::from_uri(&uri).unwrap().id, "test");
+
+        let uri = "/p".parse::<Uri>().unwrap();
+        assert!(QuerySt::<Id>::from_uri(&uri).is_err());
+        assert!(QuerySt::<Listing>::from_uri(&uri).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_strict_percent_encoding() {
+        assert_eq!(QuerySt::<Name>::from_query("name=%ZZ").unwrap().name, "%ZZ");
+
+        let config = QueryStConfig::default().strict_percent_encoding(true);
+        let s = QuerySt::<Name>::from_query_with("name=%20", &config).unwrap();
+        assert_eq!(s.name, " ");
+
+        for query in &["name=%ZZ", "name=%2", "name=%"] {
+            let err = QuerySt::<Name>::from_query_with(query, &config).unwrap_err();
+            assert!(matches!(err, QueryStPayloadError::InvalidEncoding(_, _)));
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_lossy_utf8() {
+        let s = QuerySt::<Name>::from_query("name=a%FF").unwrap();
+        assert_eq!(s.name, "a\u{FFFD}");
+
+        let config = QueryStConfig::default().lossy_utf8(false);
+        let s = QuerySt::<Name>::from_query_with("name=%C3%A9%ZZ", &config).unwrap();
+        assert_eq!(s.name, "é%ZZ");
+        let err = QuerySt::<Name>::from_query_with("name=a%FF", &config).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "QuerySt invalid encoding in query: invalid UTF-8 in the escape at byte 6"
+        );
+        assert_eq!(err.parse_errors()[0].category, ParseErrorCategory::Decoding);
+    }
+
+    #[cfg(feature = "utoipa")]
+    #[test]
+    fn test_utoipa_params() {
+        use utoipa::openapi::path::ParameterIn;
+        use utoipa::IntoParams;
+
+        #[derive(Deserialize, IntoParams)]
+        #[allow(dead_code)]
+        struct Search {
+            /// Searched text
+            q: String,
+            page: Option<u32>,
+        }
+
+        let params = QuerySt::<Search>::into_params(|| None);
+        let names = params.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["q", "page"]);
+        assert!(params.iter().all(|p| p.parameter_in == ParameterIn::Query));
+
+        let params = QuerySt::<Search>::into_params(|| Some(ParameterIn::Path));
+        assert!(params.iter().all(|p| p.parameter_in == ParameterIn::Path));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_counters() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            for uri in &["/?id=test", "/?id=other", "/?name=test"] {
+                let req = TestRequest::with_uri(uri).to_srv_request();
+                let (req, mut pl) = req.into_parts();
+                let _ = QuerySt::<Id>::from_request(&req, &mut pl).into_inner();
+            }
+        });
+
+        let counters = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect::<Vec<_>>();
+                (key.name().to_owned(), labels, value)
+            })
+            .collect::<Vec<_>>();
+        assert!(counters.contains(&(
+            "querystring_extract_success".to_owned(),
+            vec![],
+            DebugValue::Counter(2)
+        )));
+        assert!(counters.contains(&(
+            "querystring_extract_error".to_owned(),
+            vec!["variant=DeserializeType".to_owned()],
+            DebugValue::Counter(1)
+        )));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_duration() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            for uri in &["/?id=test", "/?name=test"] {
+                let req = TestRequest::with_uri(uri).to_srv_request();
+                let (req, mut pl) = req.into_parts();
+                let _ = QuerySt::<Id>::from_request(&req, &mut pl).into_inner();
+            }
+        });
+
+        let observations = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| match value {
+                DebugValue::Histogram(values)
+                    if key.key().name() == "querystring_extract_duration_seconds" =>
+                {
+                    Some(values)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(observations.len(), 2);
+        assert!(observations.iter().all(|seconds| seconds.0 >= 0.0));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[actix_rt::test]
+    #[tracing_test::traced_test]
+    async fn test_tracing_events() {
+        let req = TestRequest::with_uri("/name/user1/?id=test").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(QuerySt::<Id>::from_request(&req, &mut pl).await.is_ok());
+        assert!(logs_contain("querystring.extract"));
+        assert!(logs_contain("path=\"/name/user1/\""));
+        assert!(logs_contain("keys=1"));
+        logs_assert(|lines: &[&str]| {
+            lines
+                .iter()
+                .find(|line| line.contains("QuerySt query parsed"))
+                .filter(|line| line.contains("querystring.extract{extractor=\"QuerySt\""))
+                .map(|_| ())
+                .ok_or_else(|| "parsing wasn't logged inside the span".to_owned())
+        });
+
+        let req = TestRequest::with_uri("/name/user1/?name=test").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(QuerySt::<Id>::from_request(&req, &mut pl).await.is_err());
+        assert!(logs_contain("QuerySt extraction failed"));
+        assert!(logs_contain("error.variant=\"DeserializeType\""));
+        assert!(logs_contain("error=\"DeserializeType\""));
+
+        let req = TestRequest::with_uri("/?id[]=x").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(BoundedQuerySt::<Id, 64>::from_request(&req, &mut pl)
+            .await
+            .is_err());
+        assert!(logs_contain("extractor=\"BoundedQuerySt\""));
+    }
+
+    #[actix_rt::test]
+    async fn test_config_precedence() {
+        use actix_web::{test, web, App};
+
+        fn status_handler(status: StatusCode) -> QueryStConfig {
+            QueryStConfig::default().error_handler(move |e, _| {
+                InternalError::from_response(e, HttpResponse::build(status).finish()).into()
+            })
+        }
+
+        async fn index(info: QuerySt<Id>) -> String {
+            info.id.clone()
+        }
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(status_handler(StatusCode::UNPROCESSABLE_ENTITY))
+                .service(web::resource("/app").route(web::get().to(index)))
+                .service(
+                    web::resource("/resource")
+                        .app_data(status_handler(StatusCode::CONFLICT))
+                        .route(web::get().to(index)),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::with_uri("/app?id=test").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::with_uri("/app").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let req = test::TestRequest::with_uri("/resource").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Params {
+        page: u32,
+        per_page: u32,
+        filter: Filter,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Filter {
+        status: String,
+        tags: Vec<String>,
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_or() {
+        let defaults = serde_json::json!({
+            "page": 1,
+            "per_page": 20,
+            "filter": {"status": "open", "tags": ["a", "b"]},
+        });
+
+        let s = QuerySt::<Params>::from_query_or("page=2", defaults.clone()).unwrap();
+        assert_eq!((s.page, s.per_page), (2, 20));
+        assert_eq!(s.filter.status, "open");
+
+        let s = QuerySt::<Params>::from_query_or("filter[tags][]=c&per_page=5", defaults.clone())
+            .unwrap();
+        assert_eq!((s.page, s.per_page), (1, 5));
+        assert_eq!(s.filter.status, "open");
+        assert_eq!(s.filter.tags, vec!["c"]);
+
+        let s = QuerySt::<Params>::from_query_or("filter[status]=closed", defaults).unwrap();
+        assert_eq!(s.filter.status, "closed");
+        assert_eq!(s.filter.tags, vec!["a", "b"]);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Member {
+        name: String,
+        age: u8,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Members {
+        users: Vec<Member>,
+    }
+
+    #[actix_rt::test]
+    async fn test_array_of_objects() {
+        let s = QuerySt::<Members>::from_query("users[][name]=a&users[][age]=30").unwrap();
+        assert_eq!(
+            s.users,
+            vec![Member {
+                name: "a".to_owned(),
+                age: 30
+            }]
+        );
+
+        let s = QuerySt::<Members>::from_query(
+            "users[][name]=a&users[][age]=30&users[][name]=b&users[][age]=31",
+        )
+        .unwrap();
+        assert_eq!(s.users.len(), 2);
+        assert_eq!(s.users[1].name, "b");
+        assert_eq!(s.users[1].age, 31);
+
+        let s = QuerySt::<Members>::from_query("users[0][name]=a&users[0][age]=30").unwrap();
+        assert_eq!(s.users[0].age, 30);
+
+        let value =
+            QuerySt::<serde_json::Value>::from_query("items[0][a]=x&items[1][b]=y").unwrap();
+        assert_eq!(
+            value.into_inner(),
+            serde_json::json!({"items": [{"a": "x"}, {"b": "y"}]})
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_transform() {
+        fn uppercase(value: &mut serde_json::Value) {
+            match value {
+                serde_json::Value::String(s) => *s = s.to_uppercase(),
+                serde_json::Value::Array(items) => items.iter_mut().for_each(uppercase),
+                serde_json::Value::Object(map) => map.values_mut().for_each(uppercase),
+                _ => {}
+            }
+        }
+
+        let config = QueryStConfig::default()
+            .transform(uppercase)
+            .transform(|value| value["tags"][0] = "first".into());
+        let s = QuerySt::<serde_json::Value>::from_query_with(
+            "name=test&tags[]=a&tags[]=b&filter[status]=open&id=5",
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            s.0,
+            serde_json::json!({
+                "name": "TEST",
+                "tags": ["first", "B"],
+                "filter": {"status": "OPEN"},
+                "id": 5
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_parse_raw() {
+        let value = QuerySt::parse_raw("a[b]=1&c[]=2&d").unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"a": {"b": "1"}, "c": ["2"], "d": null})
+        );
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Nickname {
+        nickname: Option<String>,
+        age: Option<u32>,
+    }
+
+    #[actix_rt::test]
+    async fn test_null_literal() {
+        let s = QuerySt::<Nickname>::from_query("nickname=null").unwrap();
+        assert_eq!(s.nickname.as_deref(), Some("null"));
+
+        let config = QueryStConfig::default()
+            .null_literal(true)
+            .coerce_bools(true);
+        let s = QuerySt::<Nickname>::from_query_with("nickname=null&age=null", &config).unwrap();
+        assert_eq!(s.nickname, None);
+        assert_eq!(s.age, None);
+        let s = QuerySt::<Nickname>::from_query_with("nickname=Null&age=3", &config).unwrap();
+        assert_eq!(s.nickname.as_deref(), Some("Null"));
+        assert_eq!(s.age, Some(3));
+    }
+
+    #[actix_rt::test]
+    async fn test_owned_cow() {
+        #[derive(Deserialize, Debug)]
+        struct Labels {
+            name: Cow<'static, str>,
+            tags: Vec<Cow<'static, str>>,
+        }
+
+        let query = "name=a+b&tags[]=x&tags[]=y";
+        for s in [
+            QuerySt::<Labels>::from_query(query).unwrap(),
+            QuerySt::<Labels>::from_query_fast("name=a+b&tags=x&tags=y").unwrap(),
+            QuerySt::<Labels>::from_query_streaming(query).unwrap(),
+        ] {
+            assert!(matches!(s.name, Cow::Owned(_)));
+            assert_eq!(s.name, "a b");
+            assert_eq!(s.tags, vec!["x", "y"]);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_null_sentinel() {
+        fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+        where
+            D: de::Deserializer<'de>,
+            T: Deserialize<'de>,
+        {
+            Option::deserialize(deserializer).map(Some)
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Patch {
+            #[serde(default, deserialize_with = "present")]
+            nickname: Option<Option<String>>,
+            #[serde(default, deserialize_with = "present")]
+            age: Option<Option<u8>>,
+        }
+
+        let config = QueryStConfig::default().null_sentinel("__null");
+        let s = QuerySt::<Patch>::from_query_with("age=3", &config).unwrap();
+        assert_eq!(s.nickname, None);
+        assert_eq!(s.age, Some(Some(3)));
+
+        let s = QuerySt::<Patch>::from_query_with("nickname__null=1&age=3", &config).unwrap();
+        assert_eq!(s.nickname, Some(None));
+        assert_eq!(s.age, Some(Some(3)));
+
+        let s = QuerySt::<Patch>::from_query_with("age=3&age__null", &config).unwrap();
+        assert_eq!(s.age, Some(None));
+
+        let s = QuerySt::<Patch>::from_query("nickname__null=1").unwrap();
+        assert_eq!(s.nickname, None);
+    }
+
+    #[actix_rt::test]
+    async fn test_numeric_keys_to_array() {
+        #[derive(Deserialize, Debug)]
+        struct Items {
+            items: Vec<String>,
+        }
+
+        assert!(QuerySt::<Items>::from_query("items[1]=b&items[0]=a").is_err());
+
+        let config = QueryStConfig::default().numeric_keys_to_array(true);
+        let s = QuerySt::<Items>::from_query_with("items[1]=b&items[0]=a", &config).unwrap();
+        assert_eq!(s.items, vec!["a", "b"]);
+        let s = QuerySt::<Items>::from_query_with("items[0]=a&items[2]=c", &config).unwrap();
+        assert_eq!(s.items, vec!["a", "c"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_error_message_is_bounded() {
+        let long = "x".repeat(10_000);
+        let err = QuerySt::<Numbers>::from_query(&format!("id={}", long)).unwrap_err();
+        assert!(err.to_string().len() < 1024, "{}", err);
+        assert!(err.to_string().contains('…'), "{}", err);
+
+        let config = QueryStConfig::default().deny_unknown(true);
+        let err = QuerySt::<Id>::from_query_with(&format!("id=x&{}=1", long), &config).unwrap_err();
+        assert!(err.to_string().len() < 1024, "{}", err);
+
+        let err = QuerySt::<Id>::from_query("id[]=x").unwrap_err();
+        assert!(!err.to_string().contains('…'), "{}", err);
+    }
+
+    #[actix_rt::test]
+    async fn test_flatten() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Pagination {
+            page: u32,
+            per_page: u32,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Search {
+            user: Name,
+            #[serde(flatten)]
+            pagination: Pagination,
+        }
+
+        let s = QuerySt::<Search>::from_query("user[name]=x&page=2&per_page=10").unwrap();
+        assert_eq!(s.user.name, "x");
+        assert_eq!(
+            s.pagination,
+            Pagination {
+                page: 2,
+                per_page: 10
+            }
+        );
+
+        assert!(QuerySt::<Search>::from_query(
+            "user[name]=x&pagination[page]=2&pagination[per_page]=10"
+        )
+        .is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_flatten_catch_all() {
+        #[derive(Deserialize, Debug)]
+        struct Extensible {
+            id: u32,
+            #[serde(flatten)]
+            extra: HashMap<String, String>,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Open {
+            id: u32,
+            #[serde(flatten)]
+            extra: HashMap<String, serde_json::Value>,
+        }
+
+        let expected = [("foo", "bar"), ("baz", "qux")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        let s = QuerySt::<Extensible>::from_query("id=1&foo=bar&baz=qux").unwrap();
+        assert_eq!(s.id, 1);
+        assert_eq!(s.extra, expected);
+        let s = QuerySt::<Extensible>::from_query_fast("id=1&foo=bar&baz=qux").unwrap();
+        assert_eq!(s.extra, expected);
+        let s = QuerySt::<Extensible>::from_query_streaming("id=1&foo=bar&baz=qux").unwrap();
+        assert_eq!(s.extra, expected);
+        let s = QuerySt::<Extensible>::from_query("id=1").unwrap();
+        assert!(s.extra.is_empty());
+
+        for query in ["id=1&n=5", "id=1&n=1.5"] {
+            let s = QuerySt::<Extensible>::from_query(query).unwrap();
+            assert_eq!(s.extra["n"], query[7..], "{}", query);
+            let s = QuerySt::<Extensible>::from_query_fast(query).unwrap();
+            assert_eq!(s.extra["n"], query[7..], "{}", query);
+            let s = QuerySt::<Extensible>::from_query_streaming(query).unwrap();
+            assert_eq!(s.extra["n"], query[7..], "{}", query);
+        }
+        assert!(QuerySt::<Extensible>::from_query("id=1&f[x]=y").is_err());
+        let s = QuerySt::<Open>::from_query("id=1&n=5&f[x]=y&t[]=a&t[]=b").unwrap();
+        assert_eq!(s.id, 1);
+        assert_eq!(
+            serde_json::to_value(&s.extra).unwrap(),
+            serde_json::json!({"n": 5, "f": {"x": "y"}, "t": ["a", "b"]})
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_error_handler_reads_query() {
+        let req = TestRequest::with_uri("/?id[]=x")
+            .app_data(QueryStConfig::default().error_handler(|e, _| {
+                assert_eq!(e.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+                let query = match &e {
+                    QueryStPayloadError::DeserializeType(_, Some(query)) => query.clone(),
+                    _ => panic!("expected a type error along with the query, got {:?}", e),
+                };
+                assert!(!e.to_string().contains(&query), "{}", e);
+                InternalError::from_response(e, HttpResponse::Conflict().body(query)).into()
+            }))
+            .to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let err = QuerySt::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        let resp = err.as_response_error().error_response();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        match resp.body().as_ref() {
+            Some(actix_web::dev::Body::Bytes(body)) => assert_eq!(body.as_ref(), b"id[]=x"),
+            _ => panic!("expected the query as body"),
+        }
+
+        let err = QuerySt::<Id>::from_query("id[]=x").unwrap_err();
+        assert_eq!(err.query(), Some("id[]=x"));
+        let config = QueryStConfig::default().max_depth(1);
+        let err = QuerySt::<Id>::from_query_with("a[b]=1", &config).unwrap_err();
+        assert_eq!(err.query(), Some("a[b]=1"));
+        let err = QuerySt::<Id>::from_query_collect_errors("a[b=1").unwrap_err();
+        assert_eq!(err.query(), Some("a[b=1"));
+        assert_eq!(
+            ParsedQuery::parse_with("a[b]=1", &config)
+                .unwrap_err()
+                .query(),
+            Some("a[b]=1")
+        );
+        assert_eq!(QueryStPayloadError::EmptyQuery(None).query(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_max_value_bytes() {
+        let config = QueryStConfig::default().max_value_bytes(64);
+        let s = QuerySt::<serde_json::Value>::from_query_with("a[b][c]=1&d[]=2", &config);
+        assert!(s.is_ok());
+
+        // every `[]` pushes a new object, repeating the key in the parsed value
+        let query = (0..10)
+            .map(|_| "x[][some_key]=1")
+            .collect::<Vec<_>>()
+            .join("&");
+        let err = QuerySt::<serde_json::Value>::from_query_with(&query, &config).unwrap_err();
+        assert!(matches!(
+            err,
+            QueryStPayloadError::ValueTooLarge { limit: 64, .. }
+        ));
+        assert_eq!(err.kind(), QueryStErrorKind::TooLarge);
+    }
+
+    #[actix_rt::test]
+    async fn test_status_codes() {
+        let status = |e: QueryStPayloadError| e.status_code();
+        assert_eq!(
+            status(QueryStPayloadError::DeserializeValue(
+                queryst::ParseError {
+                    kind: queryst::ParseErrorKind::Other,
+                    message: "bad".to_owned(),
+                },
+                None
+            )),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(QuerySt::<Id>::from_query("id[]=x").unwrap_err()),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(QuerySt::<Id>::from_query_collect_errors("a[b=1").unwrap_err()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(QueryStPayloadError::Overflow {
+                length: 2,
+                limit: 1,
+                query: None,
+            }),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        for e in [
+            QueryStPayloadError::TooDeep {
+                limit: 1,
+                query: None,
+            },
+            QueryStPayloadError::TooManyNodes {
+                limit: 1,
+                query: None,
+            },
+            QueryStPayloadError::TooManyParams {
+                limit: 1,
+                query: None,
+            },
+            QueryStPayloadError::EmptyQuery(None),
+            QueryStPayloadError::MissingRequired(vec!["id".to_owned()], None),
+            QueryStPayloadError::ForbiddenKey("bad".to_owned(), None),
+            QueryStPayloadError::ArrayTooLong {
+                limit: 1,
+                query: None,
+            },
+            QueryStPayloadError::ValueTooLarge {
+                limit: 1,
+                query: None,
+            },
+            QueryStPayloadError::InvalidEncoding("bad".to_owned(), None),
+            QueryStPayloadError::Payload(actix_web::error::PayloadError::Incomplete(None), None),
+            QueryStPayloadError::UnknownKey("bad".to_owned(), None),
+            QueryStPayloadError::DuplicateKey("bad".to_owned(), None),
+        ] {
+            assert_eq!(status(e), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_normalize_case_for() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum ResponseType {
+            Token,
+            Code,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct AuthRequest {
+            response_type: ResponseType,
+            state: String,
+        }
+
+        let config =
+            QueryStConfig::default().normalize_case_for(&["response_type"], CaseMode::Title);
+        let s = QuerySt::<AuthRequest>::from_query_with("response_type=code&state=abc", &config)
+            .unwrap();
+        assert_eq!(s.response_type, ResponseType::Code);
+        assert_eq!(s.state, "abc");
+        let s = QuerySt::<AuthRequest>::from_query_with("response_type=TOKEN&state=x", &config)
+            .unwrap();
+        assert_eq!(s.response_type, ResponseType::Token);
+        assert!(QuerySt::<AuthRequest>::from_query("response_type=code&state=abc").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_alias() {
+        #[derive(Deserialize, Debug)]
+        struct Search {
+            query: String,
+            #[serde(default)]
+            page: u32,
+        }
+
+        let config = QueryStConfig::default()
+            .alias("q", "query")
+            .alias("p", "page")
+            .deny_unknown(true);
+        let s = QuerySt::<Search>::from_query_with("q=hello&p=2", &config).unwrap();
+        assert_eq!(s.query, "hello");
+        assert_eq!(s.page, 2);
+        let s = QuerySt::<Search>::from_query_with("q=hello&query=world", &config).unwrap();
+        assert_eq!(s.query, "world");
+        assert!(QuerySt::<Search>::from_query("q=hello").is_err());
+
+        let config = config.alias("search", "query").alias("q", "query");
+        for query in ["q=a&search=b", "search=b&q=a"] {
+            let s = QuerySt::<Search>::from_query_with(query, &config).unwrap();
+            assert_eq!(s.query, "a", "{}", query);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_and_path() {
+        #[derive(Deserialize, Debug)]
+        struct Listing {
+            id: u64,
+            sort: String,
+        }
+
+        let mut path = HashMap::new();
+        path.insert("id".to_owned(), "5".to_owned());
+        let s = QuerySt::<Listing>::from_query_and_path("sort=asc", &path).unwrap();
+        assert_eq!(s.id, 5);
+        assert_eq!(s.sort, "asc");
+
+        let s = QuerySt::<Listing>::from_query_and_path("sort=asc&id[a]=7", &path).unwrap();
+        assert_eq!(s.id, 5);
+    }
+
+    #[actix_rt::test]
+    async fn test_repeated_keys() {
+        #[derive(Deserialize, Debug)]
+        struct Tags {
+            tag: Vec<String>,
+        }
+
+        let s = QuerySt::<Tags>::from_query("tag=a&tag=b&tag=c").unwrap();
+        assert_eq!(s.tag, vec!["a", "b", "c"]);
+        let s = QuerySt::<Tags>::from_query("tag[]=a").unwrap();
+        assert_eq!(s.tag, vec!["a"]);
+        assert!(QuerySt::<Tags>::from_query("tag=a").is_err());
+
+        let s = QuerySt::<Name>::from_query("name=a").unwrap();
+        assert_eq!(s.name, "a");
+        assert!(QuerySt::<Name>::from_query("name=a&name=b").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_bare_key_merge() {
+        let err = QuerySt::<serde_json::Value>::from_query("flag&flag=1").unwrap_err();
+        assert!(matches!(&err, QueryStPayloadError::DeserializeValue(_, _)));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert!(QuerySt::<serde_json::Value>::from_query("flag&flag").is_err());
+
+        let s = QuerySt::<serde_json::Value>::from_query("flag=1&flag").unwrap();
+        assert_eq!(s.0, serde_json::json!({"flag": [1, null]}));
+    }
+
+    #[actix_rt::test]
+    async fn test_leading_question_mark() {
+        let s = QuerySt::<Id>::from_query("?id=test").unwrap();
+        assert_eq!(s.id, QuerySt::<Id>::from_query("id=test").unwrap().id);
+        let s = QuerySt::<Id>::from_query_fast("?id=test").unwrap();
+        assert_eq!(s.id, "test");
+        assert!(QuerySt::<Id>::from_query("??id=test").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_with_meta() {
+        #[derive(Deserialize, Debug)]
+        struct Search {
+            q: String,
+            filter: HashMap<String, Vec<String>>,
+        }
+
+        let (s, meta) = QuerySt::<Search>::from_query_with_meta(
+            "q=rust&filter[tag][]=web&filter[tag][]=&filter[tag][]=async&filter[lang][]=en",
+        )
+        .unwrap();
+        assert_eq!(s.q, "rust");
+        assert_eq!(s.filter["tag"], vec!["web", "async"]);
+        assert_eq!(
+            meta,
+            QueryMeta {
+                keys: 2,
+                depth: 3,
+                leaves: 4
+            }
+        );
+
+        let (_, meta) = QuerySt::<HashMap<String, String>>::from_query_with_meta("").unwrap();
+        assert_eq!(
+            meta,
+            QueryMeta {
+                keys: 0,
+                depth: 1,
+                leaves: 0
+            }
+        );
+        assert!(QuerySt::<Search>::from_query_with_meta("q=rust").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_empty_pairs() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Pair {
+            a: u32,
+            b: Option<u32>,
+        }
+
+        let expected = Pair { a: 1, b: Some(2) };
+        for query in [
+            "a=1&&b=2",
+            "a=1&b=2&",
+            "&a=1&b=2",
+            "?&a=1&b=2&&",
+            "a=1&b=2&=&",
+        ] {
+            assert_eq!(QuerySt::<Pair>::from_query(query).unwrap().0, expected);
+            assert_eq!(QuerySt::<Pair>::from_query_fast(query).unwrap().0, expected);
+            assert_eq!(
+                QuerySt::<Pair>::from_query_streaming(query).unwrap().0,
+                expected
+            );
+        }
+        for query in ["a=1&&b=2", "a=1&b=2&", "&a=1;;b=2"] {
+            let config = QueryStConfig::default()
+                .allow_semicolons(true)
+                .duplicate_policy(DuplicatePolicy::Error)
+                .max_params(2);
+            assert_eq!(
+                QuerySt::<Pair>::from_query_with(query, &config).unwrap().0,
+                expected
+            );
+            assert_eq!(
+                QuerySt::<Pair>::from_query_collect_errors(&query.replace(';', "&"))
+                    .unwrap()
+                    .0,
+                expected
+            );
+        }
+        assert!(QuerySt::<Pair>::from_query("&&&").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_duplicate_policy() {
+        assert!(QuerySt::<Id>::from_query("id=a&id=b").is_err());
+
+        let config = QueryStConfig::default().duplicate_policy(DuplicatePolicy::LastWins);
+        let s = QuerySt::<Id>::from_query_with("id=a&id=b", &config).unwrap();
+        assert_eq!(s.id, "b");
+
+        let config = QueryStConfig::default().duplicate_policy(DuplicatePolicy::FirstWins);
+        let s = QuerySt::<Id>::from_query_with("id=a&id=b", &config).unwrap();
+        assert_eq!(s.id, "a");
+
+        let config = QueryStConfig::default().duplicate_policy(DuplicatePolicy::Error);
+        let err = QuerySt::<Id>::from_query_with("id=a&id=b", &config).unwrap_err();
+        assert!(matches!(&err, QueryStPayloadError::DuplicateKey(key, _) if key == "id"));
+        assert!(QuerySt::<Ids>::from_query_with("ids[]=1&ids[]=2", &config).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_plus_as_space() {
+        let s = QuerySt::<Name>::from_query("name=John+Doe").unwrap();
+        assert_eq!(s.name, "John Doe");
+
+        let config = QueryStConfig::default().plus_as_space(false);
+        let s = QuerySt::<Name>::from_query_with("name=John+Doe", &config).unwrap();
+        assert_eq!(s.name, "John+Doe");
+        let s = QuerySt::<Name>::from_query_with("name=John%20Doe%2B", &config).unwrap();
+        assert_eq!(s.name, "John Doe+");
+    }
+
+    #[actix_rt::test]
+    async fn test_query_string_round_trip() {
+        #[derive(Deserialize, Serialize, Debug, PartialEq)]
+        struct Search {
+            q: String,
+            page: u32,
+            filter: Filter,
+            ids: Vec<u32>,
+        }
+
+        #[derive(Deserialize, Serialize, Debug, PartialEq)]
+        struct Filter {
+            name: Option<String>,
+            tag: String,
+        }
+
+        let search = QuerySt(Search {
+            q: "a&b=c d".to_owned(),
+            page: 2,
+            filter: Filter {
+                name: None,
+                tag: "x+y".to_owned(),
+            },
+            ids: vec![1, 2],
+        });
+        let query = search.try_into_query_string().unwrap();
+        assert_eq!(QuerySt::<Search>::from_query(&query).unwrap(), search);
+
+        let actix_web::web::Query(inner) = actix_web::web::Query::from(search);
+        assert_eq!(inner.page, 2);
+        assert!(QuerySt(1).try_into_query_string().is_err());
+    }
+
+    #[test]
+    fn test_to_sorted_pairs() {
+        #[derive(Serialize)]
+        struct Signed {
+            ts: u64,
+            filter: Filter,
+            ids: Vec<u32>,
+        }
+
+        #[derive(Serialize)]
+        struct Filter {
+            tag: String,
+            name: Option<String>,
+        }
+
+        let signed = QuerySt(Signed {
+            ts: 1700000000,
+            filter: Filter {
+                tag: "a b".to_owned(),
+                name: None,
+            },
+            ids: vec![3, 1],
+        });
+        let canonical = signed
+            .to_sorted_pairs()
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        assert_eq!(
+            canonical,
+            "filter[name]=&filter[tag]=a b&ids[]=3&ids[]=1&ts=1700000000"
+        );
+    }
+
+    #[test]
+    fn test_extract_sync() {
+        let req = TestRequest::with_uri("/?id=test").to_http_request();
+        assert_eq!(QuerySt::<Id>::extract_sync(&req).unwrap().id, "test");
+
+        let req = TestRequest::with_uri("/?id=test")
+            .app_data(
+                QueryStConfig::default()
+                    .max_length(2)
+                    .error_handler(|e, _| {
+                        InternalError::from_response(e, HttpResponse::Conflict().finish()).into()
+                    }),
+            )
+            .to_http_request();
+        let err = QuerySt::<Id>::extract_sync(&req).unwrap_err();
+        assert_eq!(
+            err.as_response_error().error_response().status(),
+            StatusCode::CONFLICT
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_error_kind() {
+        let kind = |q| QuerySt::<Numbers>::from_query(q).unwrap_err().kind();
+        assert_eq!(kind("ratio=1&sci=2&code=x"), QueryStErrorKind::MissingField);
+        assert_eq!(
+            kind("id=x&ratio=1&sci=2&code=x"),
+            QueryStErrorKind::TypeMismatch
+        );
+
+        let config = QueryStConfig::default().max_length(4);
+        let err = QuerySt::<Id>::from_query_with("id=test", &config).unwrap_err();
+        assert_eq!(err.kind(), QueryStErrorKind::TooLarge);
+        let err = QuerySt::<Id>::from_query_collect_errors("a[b=1").unwrap_err();
+        assert_eq!(err.kind(), QueryStErrorKind::MalformedQuery);
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_prefixed() {
+        #[derive(Deserialize, Debug)]
+        struct Filter {
+            status: String,
+            #[serde(default)]
+            owner: Option<String>,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Sort {
+            by: String,
+        }
+
+        let query = "filter[status]=open&sort[by]=date&page=2";
+        let f = QuerySt::<Filter>::from_query_prefixed(query, "filter").unwrap();
+        assert_eq!(f.status, "open");
+        assert_eq!(f.owner, None);
+        let s = QuerySt::<Sort>::from_query_prefixed(query, "sort").unwrap();
+        assert_eq!(s.by, "date");
+
+        assert!(QuerySt::<Filter>::from_query_prefixed("status=open", "filter").is_err());
+        let s = QuerySt::<Listing>::from_query_prefixed("page=2", "other").unwrap();
+        assert!(s.items.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_trim_values() {
+        let s = QuerySt::<Name>::from_query("name=%20john%20").unwrap();
+        assert_eq!(s.name, " john ");
+
+        let config = QueryStConfig::default().trim_values(true);
+        let s = QuerySt::<Name>::from_query_with("name=%20john%20", &config).unwrap();
+        assert_eq!(s.name, "john");
+        let s = QuerySt::<Ids>::from_query_with("ids[]=%201&ids[]=2%20", &config).unwrap();
+        assert_eq!(s.ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_serialize() {
+        #[derive(Serialize)]
+        struct Id {
+            id: &'static str,
+        }
+
+        let s = QuerySt(Id { id: "x" });
+        assert_eq!(serde_json::to_string(&s).unwrap(), r#"{"id":"x"}"#);
+    }
+
+    #[test]
+    fn test_normalized_json() {
+        assert_eq!(
+            QuerySt::normalized_json("a[b]=1&c[]=2").unwrap(),
+            r#"{"a":{"b":1},"c":[2]}"#
+        );
+        assert_eq!(
+            QuerySt::normalized_json("z=x&y[]=&a=007").unwrap(),
+            r#"{"a":"007","y":[],"z":"x"}"#
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_radix_literals() {
+        #[derive(Deserialize, Debug)]
+        struct Mask {
+            flags: u8,
+            mode: u32,
+            mask: u64,
+        }
+
+        let query = "flags=0xFF&mode=0o755&mask=0b1010";
+        assert!(QuerySt::<Mask>::from_query(query).is_err());
+
+        let config = QueryStConfig::default().radix_literals(true);
+        let s = QuerySt::<Mask>::from_query_with(query, &config).unwrap();
+        assert_eq!((s.flags, s.mode, s.mask), (255, 0o755, 10));
+
+        let err = QuerySt::<Mask>::from_query_with("flags=0x100&mode=1&mask=1", &config);
+        assert_eq!(err.unwrap_err().kind(), QueryStErrorKind::TypeMismatch);
+        let s = QuerySt::<Name>::from_query_with("name=0xZZ", &config).unwrap();
+        assert_eq!(s.name, "0xZZ");
+    }
+
+    #[actix_rt::test]
+    async fn test_dedup_arrays() {
+        #[derive(Deserialize, Debug)]
+        struct Tags {
+            tag: Vec<String>,
+        }
+
+        let query = "tag[]=a&tag[]=b&tag[]=a";
+        assert_eq!(QuerySt::<Tags>::from_query(query).unwrap().tag.len(), 3);
+
+        let config = QueryStConfig::default().dedup_arrays(true);
+        let s = QuerySt::<Tags>::from_query_with(query, &config).unwrap();
+        assert_eq!(s.tag, vec!["a", "b"]);
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[actix_rt::test]
+    async fn test_charset() {
+        let s = QuerySt::<Name>::from_query("name=caf%E9+cr%E8me").unwrap();
+        assert_eq!(s.name, "caf\u{fffd} cr\u{fffd}me");
+
+        let config = QueryStConfig::default().charset(encoding_rs::WINDOWS_1252);
+        let s = QuerySt::<Name>::from_query_with("name=caf%E9+cr%E8me", &config).unwrap();
+        assert_eq!(s.name, "café crème");
+        let s = QuerySt::<serde_json::Value>::from_query_with("f[n%E9]=%E9t%E9", &config).unwrap();
+        assert_eq!(s.into_inner(), serde_json::json!({"f": {"né": "été"}}));
+    }
+
+    #[actix_rt::test]
+    async fn test_bare_flags_as_true() {
+        #[derive(Deserialize, Debug)]
+        struct Verbose {
+            #[serde(default)]
+            verbose: bool,
+        }
+
+        assert!(QuerySt::<Verbose>::from_query("verbose").is_err());
+
+        let config = QueryStConfig::default()
+            .bare_flags_as_true(true)
+            .coerce_bools(true);
+        let s = QuerySt::<Verbose>::from_query_with("verbose", &config).unwrap();
+        assert!(s.verbose);
+        let s = QuerySt::<Verbose>::from_query_with("verbose=false", &config).unwrap();
+        assert!(!s.verbose);
+        let s = QuerySt::<Verbose>::from_query_with("other", &config).unwrap();
+        assert!(!s.verbose);
+        assert!(QuerySt::<Verbose>::from_query_with("verbose=", &config).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_status_for() {
+        let config = QueryStConfig::default()
+            .status_for(
+                QueryStErrorKind::TypeMismatch,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .status_for(QueryStErrorKind::MissingField, StatusCode::NOT_FOUND);
+        let status = |uri: &str, config: &QueryStConfig| {
+            let req = TestRequest::with_uri(uri)
+                .app_data(config.clone())
+                .to_srv_request();
+            let (req, _) = req.into_parts();
+            QuerySt::<Filter>::extract_sync(&req)
+                .unwrap_err()
+                .as_response_error()
+                .status_code()
+        };
+        assert_eq!(
+            status("/?status[]=a", &config),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(status("/?tags[]=a", &config), StatusCode::NOT_FOUND);
+        let only_types = QueryStConfig::default()
+            .status_for(QueryStErrorKind::TypeMismatch, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            status("/?tags[]=a", &only_types),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        let config = config.error_handler(|e, _| {
+            InternalError::from_response(e, HttpResponse::Conflict().finish()).into()
+        });
+        assert_eq!(status("/?status[]=a", &config), StatusCode::CONFLICT);
+    }
+
+    #[actix_rt::test]
+    async fn test_empty_query() {
+        for query in ["", "?", "&"] {
+            let err = QuerySt::<Id>::from_query(query).unwrap_err();
+            assert!(matches!(err, QueryStPayloadError::EmptyQuery(_)));
+            assert_eq!(err.to_string(), "QuerySt query string is required");
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        }
+        assert!(matches!(
+            QuerySt::<Id>::from_query("name=test").unwrap_err(),
+            QueryStPayloadError::DeserializeType(_, _)
+        ));
+
+        let req = TestRequest::with_uri("/").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let err = QuerySt::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_tuples() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Point(f64, f64);
+
+        #[derive(Deserialize, Debug)]
+        struct Shape {
+            point: Point,
+            range: (u32, u32),
+        }
+
+        let s = QuerySt::<Shape>::from_query("point[]=1&point[]=2.5&range[]=3&range[]=4").unwrap();
+        assert_eq!(s.point, Point(1.0, 2.5));
+        assert_eq!(s.range, (3, 4));
+
+        let s = QuerySt::<Shape>::from_query("point=1&point=2&range[0]=3&range[1]=4").unwrap();
+        assert_eq!(s.point, Point(1.0, 2.0));
+
+        let err = QuerySt::<Shape>::from_query("point[]=1&range[]=3&range[]=4").unwrap_err();
+        assert_eq!(err.kind(), QueryStErrorKind::TypeMismatch);
+        assert!(err.to_string().contains("`point`"));
+        assert!(
+            QuerySt::<Shape>::from_query("point[]=1&point[]=2&range[]=3&range[]=4&range[]=5")
+                .is_err()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_diagnostics() {
+        let req = TestRequest::with_uri("/?id=test&x=1").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(QuerySt::<Id>::from_request(&req, &mut pl).await.is_ok());
+        assert_eq!(
+            req.extensions().get::<QueryStDiagnostics>(),
+            Some(&QueryStDiagnostics {
+                query_len: 11,
+                keys: 2,
+                error: None,
+            })
+        );
+
+        let req = TestRequest::with_uri("/?id[]=test").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        assert!(QuerySt::<Id>::from_request(&req, &mut pl).await.is_err());
+        let extensions = req.extensions();
+        let diagnostics = extensions.get::<QueryStDiagnostics>().unwrap();
+        assert!(!diagnostics.is_ok());
+        assert_eq!(diagnostics.error, Some(QueryStErrorKind::TypeMismatch));
+        assert_eq!(diagnostics.keys, 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_clone_and_hash() {
+        #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+        struct Tag {
+            tag: String,
+        }
+
+        let req = TestRequest::with_uri("/?tag=rust").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = QuerySt::<Tag>::from_request(&req, &mut pl).await.unwrap();
+        let cloned = s.clone();
+        assert_eq!(cloned, s);
+
+        let mut seen = std::collections::HashSet::new();
+        assert!(seen.insert(s));
+        assert!(!seen.insert(cloned));
+    }
+
+    #[actix_rt::test]
+    async fn test_parse_errors() {
+        let err = QuerySt::<Id>::from_query_collect_errors("id=x&a[b=1&c=%zz&=v").unwrap_err();
+        let errors = err.parse_errors();
+        let categories = errors.iter().map(|e| e.category).collect::<Vec<_>>();
+        assert_eq!(
+            categories,
+            vec![
+                ParseErrorCategory::UnbalancedBrackets,
+                ParseErrorCategory::MalformedEscape,
+                ParseErrorCategory::EmptyKey,
+            ]
+        );
+        assert_eq!(errors[1].segment.as_deref(), Some("c=%zz"));
+        assert_eq!(errors[1].position, Some(2));
+        assert_eq!(errors[1].category.as_str(), "malformed_escape");
+
+        let config = QueryStConfig::default().strict_percent_encoding(true);
+        let err = QuerySt::<Id>::from_query_with("id=%g1", &config).unwrap_err();
+        let errors = err.parse_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].category, ParseErrorCategory::MalformedEscape);
+        assert_eq!(
+            (errors[0].segment.as_deref(), errors[0].position),
+            (None, Some(3))
+        );
+
+        let err = QuerySt::<Id>::from_query("id[]=x").unwrap_err();
+        assert!(err.parse_errors().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_allowed_keys() {
+        #[derive(Deserialize, Debug)]
+        struct Lookup {
+            id: u32,
+        }
+
+        let config = QueryStConfig::default().allowed_keys(&["id"]);
+        let err = QuerySt::<Lookup>::from_query_with("id=1&evil=2", &config).unwrap_err();
+        assert!(matches!(&err, QueryStPayloadError::ForbiddenKey(key, _) if key == "evil"));
+        assert_eq!(err.to_string(), "QuerySt forbidden query parameter: evil");
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        assert_eq!(
+            QuerySt::<Lookup>::from_query_with("id=1", &config)
+                .unwrap()
+                .id,
+            1
+        );
+        assert!(QuerySt::<Lookup>::from_query("id=1&evil=2").is_ok());
+        assert!(
+            QuerySt::<HashMap<String, String>>::from_query_with("id=x&evil=y", &config).is_err()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_require_keys() {
+        #[derive(Deserialize, Debug)]
+        struct Audit {
+            id: Option<u32>,
+            token: Option<String>,
+        }
+
+        let config = QueryStConfig::default().require_keys(&["id", "token"]);
+        let err = QuerySt::<Audit>::from_query_with("id=1", &config).unwrap_err();
+        assert!(matches!(
+            &err,
+            QueryStPayloadError::MissingRequired(keys, _) if keys == &["token"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "QuerySt missing required query parameters: token"
+        );
+        assert_eq!(err.kind(), QueryStErrorKind::MissingField);
+        assert!(matches!(
+            QuerySt::<Audit>::from_query_with("other=x", &config),
+            Err(QueryStPayloadError::MissingRequired(keys, _)) if keys.len() == 2
+        ));
+
+        let s = QuerySt::<Audit>::from_query_with("id=1&token", &config).unwrap();
+        assert_eq!((s.id, s.token.as_deref()), (Some(1), None));
+        assert!(QuerySt::<Audit>::from_query("id=1").is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_allow_semicolons() {
+        let s = QuerySt::<serde_json::Value>::from_query("a=1;b=2").unwrap();
+        assert_eq!(s.0, serde_json::json!({"a": "1;b=2"}));
+
+        let config = QueryStConfig::default().allow_semicolons(true);
+        let s = QuerySt::<serde_json::Value>::from_query_with("a=1;b=2&c=x%3By", &config).unwrap();
+        assert_eq!(s.0, serde_json::json!({"a": 1, "b": 2, "c": "x;y"}));
+
+        let config = config.max_params(2);
+        assert!(matches!(
+            QuerySt::<serde_json::Value>::from_query_with("a=1;b=2;c=3", &config),
+            Err(QueryStPayloadError::TooManyParams { limit: 2, .. })
+        ));
+        let config = config.duplicate_policy(DuplicatePolicy::FirstWins);
+        let s = QuerySt::<serde_json::Value>::from_query_with("a=1;a=2", &config).unwrap();
+        assert_eq!(s.0, serde_json::json!({"a": 1}));
+    }
+
+    #[actix_rt::test]
+    async fn test_key_notation() {
+        #[derive(Deserialize, Debug)]
+        struct User {
+            name: String,
+            age: u8,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Profile {
+            user: User,
+            site: String,
+        }
+
+        let query = "user.name=x&user.age=30&site=example.org";
+        assert!(QuerySt::<Profile>::from_query(query).is_err());
+
+        let config = QueryStConfig::default().key_notation(Notation::Dot);
+        let s = QuerySt::<Profile>::from_query_with(query, &config).unwrap();
+        assert_eq!(s.user.name, "x");
+        assert_eq!(s.user.age, 30);
+        assert_eq!(s.site, "example.org");
+
+        let s =
+            QuerySt::<Profile>::from_query_with("user[name]=x&user.age=1&site=a", &config).unwrap();
+        assert_eq!(s.user.name, "x");
+        assert_eq!(s.user.age, 1);
+
+        let s = QuerySt::<serde_json::Value>::from_query_with("a%2Eb=1", &config).unwrap();
+        assert_eq!(s.0, serde_json::json!({"a.b": 1}));
+
+        let config = config.duplicate_policy(DuplicatePolicy::LastWins);
+        let s = QuerySt::<User>::from_query_with("name=x&age=1&age=2", &config).unwrap();
+        assert_eq!(s.age, 2);
+        let s = QuerySt::<Profile>::from_query_with(
+            "user[name]=x&user.name=y&user.age=1&site=a",
+            &config,
+        )
+        .unwrap();
+        assert_eq!(s.user.name, "y");
+    }
+
+    #[actix_rt::test]
+    async fn test_sequence_hint() {
+        #[derive(Deserialize, Debug)]
+        struct Items {
+            items: Vec<u32>,
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        let err = QuerySt::<Items>::from_query("items=5").unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("; expected array, did you mean `items[]=5`?"),
+            "{}",
+            err
+        );
+        let err = QuerySt::<Items>::from_query("items[]=5&tags=rust").unwrap_err();
+        assert!(err.to_string().contains("`tags[]=rust`"), "{}", err);
+        let err = QuerySt::<Family>::from_query("user[ids]=1").unwrap_err();
+        assert!(err.to_string().contains("`user[ids][]=1`"), "{}", err);
+
+        let s = QuerySt::<Items>::from_query("items[]=5&tags[]=rust").unwrap();
+        assert_eq!(s.items, vec![5]);
+        assert_eq!(s.tags, vec!["rust"]);
+
+        let err = QuerySt::<Items>::from_query("items[]=x").unwrap_err();
+        assert!(!err.to_string().contains("did you mean"), "{}", err);
+    }
+
+    #[actix_rt::test]
+    async fn test_from_query_timed() {
+        let query = (0..200)
+            .map(|i| format!("ids[]={}", i))
+            .collect::<Vec<_>>()
+            .join("&");
+        let (result, _elapsed): (_, Duration) = QuerySt::<Ids>::from_query_timed(&query);
+        assert_eq!(result.unwrap().ids.len(), 200);
+
+        let (result, _) = QuerySt::<Ids>::from_query_timed("ids=x");
+        assert!(result.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_collect_errors() {
+        let s = QuerySt::<Id>::from_query_collect_errors("id=test&&").unwrap();
+        assert_eq!(s.id, "test");
+
+        let err = QuerySt::<Id>::from_query_collect_errors("a[b=1&id=test&c=%ZZ").unwrap_err();
+        match &err {
+            QueryStPayloadError::Multiple(errors, _) => assert_eq!(errors.len(), 2),
+            _ => panic!("expected multiple errors"),
+        }
+        assert!(err.to_string().contains("`a[b=1`"), "{}", err);
+        assert!(err.to_string().contains("`c=%ZZ`"), "{}", err);
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
     #[actix_rt::test]
     async fn test_custom_error_responder() {
         let req = TestRequest::with_uri("/name/user1/")
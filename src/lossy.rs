@@ -0,0 +1,162 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, web::HttpRequest, FromRequest};
+use serde::de;
+
+use crate::{parse_req, parse_value_with, Extraction, QuerySt, QueryStConfig, QueryStPayloadError};
+
+/// Extract information from the request's query using `queryst`, keeping the parsed value
+/// and the deserialization outcome together.
+///
+/// This extractor never rejects a request. `value` holds the query as parsed by `queryst`
+/// (or `null` if the query string itself was rejected), and `result` holds either the
+/// deserialized `T` or the error explaining why it couldn't be built.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::LossyQuerySt;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Search {
+///    term: String,
+///    page: u32,
+/// }
+///
+/// async fn index(query: LossyQuerySt<Search>) -> String {
+///     match query.result {
+///         Ok(search) => format!("Searching {} on page {}", search.term, search.page),
+///         Err(e) => format!("Bad search {}: {}", query.value, e),
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/search").route(web::get().to(index)));
+/// }
+/// ```
+pub struct LossyQuerySt<T> {
+    /// The query as parsed by `queryst`, before any coercion
+    pub value: serde_json::Value,
+    /// The outcome of deserializing `value` into `T`
+    pub result: Result<T, QueryStPayloadError>,
+}
+
+impl<T> LossyQuerySt<T>
+where
+    T: de::DeserializeOwned,
+{
+    /// Parse `query_str` with the given config, without short-circuiting on failure
+    pub fn from_query_with(query_str: &str, config: &QueryStConfig) -> Self {
+        Self::from_parsed(parse_value_with(query_str, config), config).recording_query(query_str)
+    }
+
+    fn from_parsed(
+        parsed: Result<serde_json::Value, QueryStPayloadError>,
+        config: &QueryStConfig,
+    ) -> Self {
+        match parsed {
+            Ok(value) => LossyQuerySt {
+                result: QuerySt::from_value_with(value.clone(), config).map(QuerySt::into_inner),
+                value,
+            },
+            Err(e) => LossyQuerySt {
+                value: serde_json::Value::Null,
+                result: Err(e),
+            },
+        }
+    }
+
+    fn recording_query(self, query_str: &str) -> Self {
+        LossyQuerySt {
+            result: self.result.map_err(|e| e.with_query(query_str)),
+            ..self
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LossyQuerySt<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LossyQuerySt")
+            .field("value", &self.value)
+            .field("result", &self.result)
+            .finish()
+    }
+}
+
+impl<T> FromRequest for LossyQuerySt<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Infallible>>;
+    type Config = QueryStConfig;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = QueryStConfig::from_req(req);
+        let mut extraction = Extraction::start(req, "LossyQuerySt", req.query_string().len());
+        let s = extraction.run(|extraction| {
+            let parsed = parse_req(req, config);
+            if let Ok(value) = &parsed {
+                extraction.parsed(value);
+            }
+            Self::from_parsed(parsed, config).recording_query(req.query_string())
+        });
+        extraction.finish(req, &s.result);
+        ready(Ok(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct User {
+        name: String,
+        siblings: Vec<String>,
+    }
+
+    #[actix_rt::test]
+    async fn test_lossy_request_extract() {
+        let req = TestRequest::with_uri("/?siblings[]=hasan&siblings[]=ahmad").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = LossyQuerySt::<User>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert!(matches!(
+            s.result,
+            Err(QueryStPayloadError::DeserializeType(_, _))
+        ));
+        assert_eq!(s.value, json!({"siblings": ["hasan", "ahmad"]}));
+
+        let req = TestRequest::with_uri("/?name=test&siblings[]=hasan").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = LossyQuerySt::<User>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        let user = s.result.unwrap();
+        assert_eq!(user.name, "test");
+        assert_eq!(user.siblings, vec!["hasan"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_lossy_rejected_query() {
+        let config = QueryStConfig::default().max_length(4);
+        let s = LossyQuerySt::<User>::from_query_with("name=test", &config);
+        assert_eq!(s.value, serde_json::Value::Null);
+        assert!(matches!(
+            s.result,
+            Err(QueryStPayloadError::Overflow { .. })
+        ));
+    }
+}
@@ -0,0 +1,861 @@
+//! Passes that check or rewrite the `serde_json::Value` produced by `queryst::parse` before it
+//! is deserialized into the target type.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Number, Value};
+
+use crate::config::BoolTokens;
+use crate::raw::{ArraySyntax, Segment};
+use crate::{CaseMode, QueryStPayloadError};
+
+/// Call `f` on every leaf (anything but arrays and objects) of the tree.
+///
+/// The walk uses an explicit stack so deeply nested queries can't overflow the call stack.
+fn for_each_leaf_mut<F>(value: &mut Value, mut f: F)
+where
+    F: FnMut(&mut Value),
+{
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(items) => stack.extend(items.iter_mut()),
+            Value::Object(map) => stack.extend(map.values_mut()),
+            leaf => f(leaf),
+        }
+    }
+}
+
+/// Limits on the shape of the parsed tree, checked by `check_limits`
+#[derive(Default)]
+pub(crate) struct Limits {
+    /// Nodes of any kind, arrays and objects included
+    pub(crate) nodes: Option<usize>,
+    /// Levels of nested arrays and objects, the top-level value counting as the first one
+    pub(crate) depth: Option<usize>,
+    /// Elements of a single array
+    pub(crate) array_len: Option<usize>,
+    /// Bytes of the JSON serialization of the tree
+    pub(crate) value_bytes: Option<usize>,
+}
+
+impl Limits {
+    fn is_empty(&self) -> bool {
+        self.nodes.is_none()
+            && self.depth.is_none()
+            && self.array_len.is_none()
+            && self.value_bytes.is_none()
+    }
+}
+
+/// Check the tree against every limit of `limits` in a single walk.
+///
+/// The walk stops as soon as the node budget is exhausted. Otherwise the whole tree is
+/// walked and, when several limits are exceeded, the depth is reported first, then the
+/// array length and then the size. The size is estimated from the lengths of keys and
+/// strings plus the JSON punctuation, ignoring escapes.
+pub(crate) fn check_limits(value: &Value, limits: &Limits) -> Result<(), QueryStPayloadError> {
+    if limits.is_empty() {
+        return Ok(());
+    }
+    let (mut nodes, mut depth, mut array_len, mut size) = (0usize, 0, 0, 0usize);
+    let mut stack = vec![(value, 1)];
+    while let Some((value, level)) = stack.pop() {
+        nodes += 1;
+        if let Some(limit) = limits.nodes.filter(|&limit| nodes > limit) {
+            return Err(QueryStPayloadError::TooManyNodes { limit, query: None });
+        }
+        size += match value {
+            Value::Null => 4,
+            Value::Bool(b) => 4 + !b as usize,
+            Value::Number(n) if limits.value_bytes.is_some() => n.to_string().len(),
+            Value::Number(_) => 0,
+            Value::String(s) => s.len() + 2,
+            Value::Array(items) => {
+                depth = depth.max(level);
+                array_len = array_len.max(items.len());
+                stack.extend(items.iter().map(|item| (item, level + 1)));
+                2 + items.len().saturating_sub(1)
+            }
+            Value::Object(map) => {
+                depth = depth.max(level);
+                stack.extend(map.values().map(|item| (item, level + 1)));
+                2 + map.len().saturating_sub(1) + map.keys().map(|k| k.len() + 3).sum::<usize>()
+            }
+        };
+    }
+
+    if let Some(limit) = limits.depth.filter(|&limit| depth > limit) {
+        return Err(QueryStPayloadError::TooDeep { limit, query: None });
+    }
+    if let Some(limit) = limits.array_len.filter(|&limit| array_len > limit) {
+        return Err(QueryStPayloadError::ArrayTooLong { limit, query: None });
+    }
+    if let Some(limit) = limits.value_bytes.filter(|&limit| size > limit) {
+        return Err(QueryStPayloadError::ValueTooLarge { limit, query: None });
+    }
+    Ok(())
+}
+
+/// Call `f` on every array of the tree, along with its path as `ArraySyntax` identifies it,
+/// before walking its elements.
+fn for_each_array_mut<F>(value: &mut Value, mut f: F)
+where
+    F: FnMut(&mut Vec<Value>, &[Segment]),
+{
+    let mut stack = vec![(value, Vec::new())];
+    while let Some((value, mut path)) = stack.pop() {
+        match value {
+            Value::Array(items) => {
+                f(items, &path);
+                path.push(Segment::Index);
+                stack.extend(items.iter_mut().map(|item| (item, path.clone())));
+            }
+            Value::Object(map) => stack.extend(map.iter_mut().map(|(key, item)| {
+                let mut path = path.clone();
+                path.push(Segment::Key(key.clone()));
+                (item, path)
+            })),
+            _ => {}
+        }
+    }
+}
+
+/// Remove the empty strings the no-value `items[]=` syntax pushed into arrays.
+///
+/// Empty values of repeated keys (ex: `t=a&t=`) are kept, only as many empty strings as there
+/// were `[]=` pairs for an array are removed, the first ones.
+pub(crate) fn drop_empty_array_items(value: &mut Value, syntax: &ArraySyntax) {
+    if syntax.empty_pushes.is_empty() {
+        return;
+    }
+    let mut remaining = syntax.empty_pushes.clone();
+    for_each_array_mut(value, |items, path| {
+        if let Some(count) = remaining.get_mut(path) {
+            items.retain(|item| match item {
+                Value::String(s) if s.is_empty() && *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            });
+        }
+    });
+}
+
+/// Merge consecutive objects of arrays as long as their keys don't collide, since
+/// `users[][name]=a&users[][age]=30` pushes a new object for each pair.
+///
+/// A key already set on the previous object starts a new one, so
+/// `users[][name]=a&users[][age]=1&users[][name]=b` gives two users. Only the arrays whose
+/// objects were all pushed with `[]` are merged, explicit indices (ex: `users[0][name]=a`)
+/// already say which object a pair belongs to.
+pub(crate) fn merge_array_objects(value: &mut Value, syntax: &ArraySyntax) {
+    if !syntax.has_pushes() {
+        return;
+    }
+    for_each_array_mut(value, |items, path| {
+        if !syntax.only_pushed(path) || items.iter().filter(|item| item.is_object()).count() < 2 {
+            return;
+        }
+        let mut merged: Vec<Value> = Vec::with_capacity(items.len());
+        for item in items.drain(..) {
+            if let (Some(Value::Object(last)), Value::Object(map)) = (merged.last_mut(), &item) {
+                if !map.keys().any(|key| last.contains_key(key)) {
+                    if let Value::Object(map) = item {
+                        last.extend(map);
+                    }
+                    continue;
+                }
+            }
+            merged.push(item);
+        }
+        *items = merged;
+    });
+}
+
+/// Split string leaves containing `delimiter` into arrays of their segments.
+///
+/// Strings that are already array elements are spliced into their array, so `a[]=1,2&a[]=3`
+/// gives a single flat list. Empty segments are kept as empty strings.
+pub(crate) fn split_on(value: &mut Value, delimiter: char) {
+    let split = |s: &str| {
+        s.split(delimiter)
+            .map(|segment| Value::String(segment.to_owned()))
+            .collect::<Vec<_>>()
+    };
+    let needs_split = |value: &Value| matches!(value, Value::String(s) if s.contains(delimiter));
+
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(items) => {
+                if items.iter().any(needs_split) {
+                    let mut spliced = Vec::with_capacity(items.len());
+                    for item in items.drain(..) {
+                        match item {
+                            Value::String(s) if s.contains(delimiter) => spliced.extend(split(&s)),
+                            item => spliced.push(item),
+                        }
+                    }
+                    *items = spliced;
+                }
+                stack.extend(items.iter_mut());
+            }
+            Value::Object(map) => {
+                for value in map.values_mut() {
+                    match value {
+                        Value::String(s) if s.contains(delimiter) => {
+                            *value = Value::Array(split(s))
+                        }
+                        value => stack.push(value),
+                    }
+                }
+            }
+            Value::String(s) if s.contains(delimiter) => *value = Value::Array(split(s)),
+            _ => {}
+        }
+    }
+}
+
+/// Rename the top-level keys found in `aliases` to the key they map to.
+///
+/// When the target key is present as well, it is kept and the aliased value is dropped, and
+/// of several aliases of the same key the first one in `aliases` is kept. Aliases are not
+/// chained, a key is renamed at most once.
+pub(crate) fn rename_keys(value: &mut Value, aliases: &[(String, String)]) {
+    if let Value::Object(map) = value {
+        let renamed = aliases
+            .iter()
+            .filter_map(|(old, new)| map.remove(old).map(|aliased| (new, aliased)))
+            .collect::<Vec<_>>();
+        for (new, aliased) in renamed {
+            map.entry(new.clone()).or_insert(aliased);
+        }
+    }
+}
+
+/// Replace each top-level key ending with `suffix` by its prefix set to `null`, whatever
+/// the value of the sentinel and of the prefix key.
+///
+/// A key that is exactly `suffix` is left as is.
+pub(crate) fn null_sentinels(value: &mut Value, suffix: &str) {
+    if let Value::Object(map) = value {
+        let fields = map
+            .keys()
+            .filter_map(|key| key.strip_suffix(suffix))
+            .filter(|field| !field.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        for field in fields {
+            map.remove(&format!("{}{}", field, suffix));
+            map.insert(field, Value::Null);
+        }
+    }
+}
+
+/// Turn objects whose keys are all array indices (ex: from `items[0]=a&items[2]=c`) into
+/// arrays.
+///
+/// Elements are ordered by index and gaps are compacted rather than filled with `null`, so
+/// `items[0]=a&items[2]=c` gives `["a", "c"]`. This keeps `items[1000000]=x` from allocating
+/// a huge array. Only canonical indices count, an object with a key like `01` is left as is.
+pub(crate) fn numeric_keys_to_array(value: &mut Value) {
+    let index = |key: &str| match key.as_bytes() {
+        [b'0'] => Some(0),
+        [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => {
+            key.parse::<usize>().ok()
+        }
+        _ => None,
+    };
+
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        if let Value::Object(map) = value {
+            if !map.is_empty() && map.keys().all(|key| index(key).is_some()) {
+                let mut items = std::mem::take(map)
+                    .into_iter()
+                    .map(|(key, value)| (index(&key).unwrap_or_default(), value))
+                    .collect::<Vec<_>>();
+                items.sort_by_key(|(i, _)| *i);
+                *value = Value::Array(items.into_iter().map(|(_, value)| value).collect());
+            }
+        }
+        match value {
+            Value::Array(items) => stack.extend(items.iter_mut()),
+            Value::Object(map) => stack.extend(map.values_mut()),
+            _ => {}
+        }
+    }
+}
+
+/// Merge `value` over `base`: objects are merged key by key, anything else from `value`
+/// replaces what `base` has.
+///
+/// This only recurses where both sides are objects, so it never goes deeper than `base`.
+pub(crate) fn merge_over(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Object(base), Value::Object(map)) => {
+            for (key, value) in map {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_over(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// Trim leading and trailing whitespace from string leaves.
+pub(crate) fn trim_values(value: &mut Value) {
+    for_each_leaf_mut(value, |leaf| {
+        if let Value::String(s) = leaf {
+            let trimmed = s.trim();
+            if trimmed.len() != s.len() {
+                *s = trimmed.to_owned();
+            }
+        }
+    });
+}
+
+/// Remove repeated elements from arrays of plain values, keeping the first occurrence.
+///
+/// Arrays holding arrays or objects are left as they are.
+pub(crate) fn dedup_arrays(value: &mut Value) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(items) => {
+                if items.iter().any(|i| i.is_array() || i.is_object()) {
+                    stack.extend(items.iter_mut());
+                } else {
+                    let mut seen = HashSet::new();
+                    items.retain(|item| seen.insert(item.to_string()));
+                }
+            }
+            Value::Object(map) => stack.extend(map.values_mut()),
+            _ => {}
+        }
+    }
+}
+
+/// Replace `null` leaves, which is what `queryst` gives keys without a value, with `true`.
+pub(crate) fn bare_flags_as_true(value: &mut Value) {
+    for_each_leaf_mut(value, |leaf| {
+        if leaf.is_null() {
+            *leaf = Value::Bool(true);
+        }
+    });
+}
+
+/// Value coercions applied to every string leaf by `coerce_leaves`
+#[derive(Default)]
+pub(crate) struct Coercions<'a> {
+    /// Replace `""` with `null`
+    pub(crate) empty_as_null: bool,
+    /// Replace `"null"` with `null`
+    pub(crate) null_literal: bool,
+    /// Case to rewrite the strings under some top-level keys in
+    pub(crate) case_modes: Option<&'a HashMap<String, CaseMode>>,
+    /// Convert `"true"`/`"false"` into booleans, and `"1"`/`"0"` too if set to `Some(true)`
+    pub(crate) bools: Option<bool>,
+    /// Strings converted by `bools` instead of `"true"`/`"false"`, ignoring ASCII case
+    pub(crate) bool_tokens: Option<&'a BoolTokens>,
+    /// Convert `0x`, `0o` and `0b` prefixed strings into numbers
+    pub(crate) radix_literals: bool,
+}
+
+/// Apply `coercions` to every string leaf in a single walk.
+///
+/// Each leaf goes through the coercions in the order of the fields of `Coercions`, and stops
+/// at the first one that turns it into something other than a string. Only unsigned radix
+/// literals fitting in 64 bits are converted. Numeric coercion isn't one of them, it depends
+/// on the target type and happens while deserializing, see `coerced`.
+pub(crate) fn coerce_leaves(value: &mut Value, coercions: &Coercions<'_>) {
+    let mut stack = Vec::new();
+    match value {
+        Value::Object(map) => stack.extend(map.iter_mut().map(|(key, value)| {
+            let mode = coercions
+                .case_modes
+                .and_then(|modes| modes.get(key))
+                .copied();
+            (value, mode)
+        })),
+        value => stack.push((value, None)),
+    }
+    while let Some((value, mode)) = stack.pop() {
+        match value {
+            Value::Array(items) => stack.extend(items.iter_mut().map(|item| (item, mode))),
+            Value::Object(map) => stack.extend(map.values_mut().map(|item| (item, mode))),
+            leaf => coerce_leaf(leaf, mode, coercions),
+        }
+    }
+}
+
+fn coerce_leaf(leaf: &mut Value, mode: Option<CaseMode>, coercions: &Coercions<'_>) {
+    let s = match leaf {
+        Value::String(s) => s,
+        _ => return,
+    };
+    if (coercions.empty_as_null && s.is_empty()) || (coercions.null_literal && s == "null") {
+        *leaf = Value::Null;
+        return;
+    }
+    if let Some(mode) = mode {
+        *s = mode.apply(s);
+    }
+    if let Some(digits) = coercions.bools {
+        let is_token = |tokens: &[String]| tokens.iter().any(|t| t.eq_ignore_ascii_case(s));
+        let b = match (s.as_str(), coercions.bool_tokens) {
+            ("1", _) if digits => Some(true),
+            ("0", _) if digits => Some(false),
+            (_, Some(tokens)) if is_token(&tokens.truthy) => Some(true),
+            (_, Some(tokens)) if is_token(&tokens.falsy) => Some(false),
+            (_, Some(_)) => None,
+            ("true", None) => Some(true),
+            ("false", None) => Some(false),
+            _ => None,
+        };
+        if let Some(b) = b {
+            *leaf = Value::Bool(b);
+            return;
+        }
+    }
+    if coercions.radix_literals {
+        if let Some(number) = parse_radix(s) {
+            *leaf = Value::Number(number.into());
+        }
+    }
+}
+
+fn parse_radix(s: &str) -> Option<u64> {
+    let radix = match s.get(..2)? {
+        "0x" => 16,
+        "0o" => 8,
+        "0b" => 2,
+        _ => return None,
+    };
+    let digits = &s[2..];
+    // `from_str_radix` accepts a sign, which isn't part of these literals
+    if digits.starts_with('+') {
+        return None;
+    }
+    u64::from_str_radix(digits, radix).ok()
+}
+
+/// Parse a string that fully matches the JSON number grammar, so ambiguous values like `007`
+/// or `0x10` aren't numbers, neither are integers that don't fit in 64 bits and floats that
+/// aren't finite.
+pub(crate) fn parse_number(s: &str) -> Option<Number> {
+    let bytes = s.as_bytes();
+    let digits = |mut i: usize| {
+        let start = i;
+        while matches!(bytes.get(i), Some(b) if b.is_ascii_digit()) {
+            i += 1;
+        }
+        (i, i > start)
+    };
+
+    let mut i = 0;
+    if bytes.first() == Some(&b'-') {
+        i += 1;
+    }
+    match bytes.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => i = digits(i).0,
+        _ => return None,
+    }
+
+    let mut integer = true;
+    if bytes.get(i) == Some(&b'.') {
+        let (end, any) = digits(i + 1);
+        if !any {
+            return None;
+        }
+        integer = false;
+        i = end;
+    }
+    if let Some(b'e') | Some(b'E') = bytes.get(i) {
+        i += 1;
+        if let Some(b'+') | Some(b'-') = bytes.get(i) {
+            i += 1;
+        }
+        let (end, any) = digits(i);
+        if !any {
+            return None;
+        }
+        integer = false;
+        i = end;
+    }
+    if i != bytes.len() {
+        return None;
+    }
+
+    if integer {
+        s.parse::<u64>()
+            .map(Number::from)
+            .or_else(|_| s.parse::<i64>().map(Number::from))
+            .ok()
+    } else {
+        s.parse::<f64>().ok().and_then(Number::from_f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("64"), Some(64u64.into()));
+        assert_eq!(parse_number("-3"), Some((-3i64).into()));
+        assert_eq!(parse_number("-2.75"), Number::from_f64(-2.75));
+        assert_eq!(parse_number("1e5"), Number::from_f64(1e5));
+        assert_eq!(parse_number("0"), Some(0u64.into()));
+        assert_eq!(parse_number("0.5"), Number::from_f64(0.5));
+
+        for s in &[
+            "007",
+            "0x10",
+            "",
+            "-",
+            "1.",
+            ".5",
+            "1e",
+            "1e+",
+            "+1",
+            "1 ",
+            "abc",
+            "1e400",
+            "99999999999999999999",
+        ] {
+            assert_eq!(parse_number(s), None, "{:?} shouldn't be a number", s);
+        }
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let check = |value: &Value, limits| check_limits(value, &limits).map_err(|e| e.to_string());
+
+        let value = json!({"a": {"b": ["c"]}, "d": "e"});
+        let depth = |limit| Limits {
+            depth: Some(limit),
+            ..Limits::default()
+        };
+        assert!(check(&value, depth(3)).is_ok());
+        assert!(matches!(
+            check_limits(&value, &depth(2)),
+            Err(QueryStPayloadError::TooDeep { limit: 2, .. })
+        ));
+        assert!(check(&json!("leaf"), depth(0)).is_ok());
+
+        let value = json!({"a": ["1", "2"], "b": {"c": [["3", "4", "5"]]}});
+        let array_len = |limit| Limits {
+            array_len: Some(limit),
+            ..Limits::default()
+        };
+        assert!(check(&value, array_len(3)).is_ok());
+        assert!(matches!(
+            check_limits(&value, &array_len(2)),
+            Err(QueryStPayloadError::ArrayTooLong { limit: 2, .. })
+        ));
+        assert!(check(&json!({"a": "1", "b": "2", "c": "3"}), array_len(2)).is_ok());
+
+        let value = json!({"a": ["1", null], "b": {"c": true, "d": 10}});
+        let size = value.to_string().len();
+        let value_bytes = |limit| Limits {
+            value_bytes: Some(limit),
+            ..Limits::default()
+        };
+        assert!(check(&value, value_bytes(size)).is_ok());
+        assert!(matches!(
+            check_limits(&value, &value_bytes(size - 1)),
+            Err(QueryStPayloadError::ValueTooLarge { .. })
+        ));
+
+        let nodes = |limit| Limits {
+            nodes: Some(limit),
+            depth: Some(1),
+            ..Limits::default()
+        };
+        assert!(matches!(
+            check_limits(&value, &nodes(7)),
+            Err(QueryStPayloadError::TooDeep { limit: 1, .. })
+        ));
+        assert!(matches!(
+            check_limits(&value, &nodes(6)),
+            Err(QueryStPayloadError::TooManyNodes { limit: 6, .. })
+        ));
+        assert!(check(&value, Limits::default()).is_ok());
+    }
+
+    #[test]
+    fn test_drop_empty_array_items() {
+        let query = "a[]=&a[]=x&a[]=&b=&c[d][]=&t=a&t=&u[]=&u=";
+        let syntax = crate::raw::check_keys(query).unwrap();
+        let mut value = queryst::parse(query).unwrap();
+        drop_empty_array_items(&mut value, &syntax);
+        assert_eq!(
+            value,
+            json!({"a": ["x"], "b": "", "c": {"d": []}, "t": ["a", ""], "u": [""]})
+        );
+    }
+
+    #[test]
+    fn test_merge_array_objects() {
+        let query = "users[][name]=a&users[][age]=1&users[][name]=b&users[][age]=2\
+            &x[y][][a]=1&x[y][]=z&x[y][][b]=2&x[y][][c][][d]=3&x[y][][c][][e]=4";
+        let syntax = crate::raw::check_keys(query).unwrap();
+        let mut value = json!({
+            "users": [{"name": "a"}, {"age": "1"}, {"name": "b"}, {"age": "2"}],
+            "x": {"y": [{"a": "1"}, "z", {"b": "2"}, {"c": [{"d": "3"}, {"e": "4"}]}]}
+        });
+        merge_array_objects(&mut value, &syntax);
+        assert_eq!(
+            value,
+            json!({
+                "users": [{"name": "a", "age": "1"}, {"name": "b", "age": "2"}],
+                "x": {"y": [{"a": "1"}, "z", {"b": "2", "c": [{"d": "3", "e": "4"}]}]}
+            })
+        );
+
+        let syntax = crate::raw::check_keys("items[0][a]=x&items[1][b]=y").unwrap();
+        let mut value = json!({"items": [{"a": "x"}, {"b": "y"}]});
+        let expected = value.clone();
+        merge_array_objects(&mut value, &syntax);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_split_on() {
+        let mut value = json!({"a": "1,2", "b": ["3,,4", "5"], "c": {"d": "x"}, "e": [["6,7"]]});
+        split_on(&mut value, ',');
+        assert_eq!(
+            value,
+            json!({"a": ["1", "2"], "b": ["3", "", "4", "5"], "c": {"d": "x"}, "e": [["6", "7"]]})
+        );
+    }
+
+    #[test]
+    fn test_rename_keys() {
+        let aliases = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(old, new)| (old.to_string(), new.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let mut value = json!({"q": "x", "p": "1", "page": "2", "a": {"q": "y"}});
+        rename_keys(&mut value, &aliases(&[("q", "query"), ("p", "page")]));
+        assert_eq!(value, json!({"query": "x", "page": "2", "a": {"q": "y"}}));
+
+        for (pairs, expected) in [
+            (&[("s", "search"), ("q", "search")], "a"),
+            (&[("q", "search"), ("s", "search")], "b"),
+        ] {
+            let mut value = json!({"q": "b", "s": "a"});
+            rename_keys(&mut value, &aliases(pairs));
+            assert_eq!(value, json!({"search": expected}));
+        }
+    }
+
+    #[test]
+    fn test_null_sentinels() {
+        let mut value = json!({
+            "a__null": "1",
+            "b": "x",
+            "b__null": null,
+            "c": "y",
+            "__null": "z",
+            "d": {"e__null": "1"},
+        });
+        null_sentinels(&mut value, "__null");
+        assert_eq!(
+            value,
+            json!({"a": null, "b": null, "c": "y", "__null": "z", "d": {"e__null": "1"}})
+        );
+    }
+
+    #[test]
+    fn test_normalize_case() {
+        let modes = [
+            ("a", CaseMode::Title),
+            ("b", CaseMode::Upper),
+            ("c", CaseMode::Lower),
+        ]
+        .iter()
+        .map(|(key, mode)| (key.to_string(), *mode))
+        .collect();
+        let mut value = json!({
+            "a": ["cODE", "éa", ""],
+            "b": {"x": "tOken"},
+            "c": "ID_Token",
+            "d": "Code",
+            "e": {"a": "code"},
+        });
+        let coercions = Coercions {
+            case_modes: Some(&modes),
+            ..Coercions::default()
+        };
+        coerce_leaves(&mut value, &coercions);
+        assert_eq!(
+            value,
+            json!({
+                "a": ["Code", "Éa", ""],
+                "b": {"x": "TOKEN"},
+                "c": "id_token",
+                "d": "Code",
+                "e": {"a": "code"},
+            })
+        );
+    }
+
+    #[test]
+    fn test_numeric_keys_to_array() {
+        let mut value =
+            json!({"a": {"1": "y", "0": "x"}, "b": {"c": [{"0": {"10": "z", "2": "w"}}]}});
+        numeric_keys_to_array(&mut value);
+        assert_eq!(value, json!({"a": ["x", "y"], "b": {"c": [[["w", "z"]]]}}));
+
+        let mut value = json!({"a": {"0": "x", "b": "y"}, "c": {"01": "z"}, "d": {}});
+        numeric_keys_to_array(&mut value);
+        assert_eq!(
+            value,
+            json!({"a": {"0": "x", "b": "y"}, "c": {"01": "z"}, "d": {}})
+        );
+    }
+
+    #[test]
+    fn test_merge_over() {
+        let mut base = json!({"a": 1, "b": {"c": 2, "d": [3]}, "e": {"f": 4}});
+        merge_over(
+            &mut base,
+            json!({"a": 5, "b": {"d": [6, 7], "g": 8}, "e": "h"}),
+        );
+        assert_eq!(
+            base,
+            json!({"a": 5, "b": {"c": 2, "d": [6, 7], "g": 8}, "e": "h"})
+        );
+    }
+
+    #[test]
+    fn test_trim_values() {
+        let mut value = json!({"a": " x ", "b": ["\ty", "z"], "c": {"d": "  "}});
+        trim_values(&mut value);
+        assert_eq!(value, json!({"a": "x", "b": ["y", "z"], "c": {"d": ""}}));
+    }
+
+    #[test]
+    fn test_dedup_arrays() {
+        let mut value = json!({
+            "a": ["x", "y", "x", 1, "1", 1],
+            "b": {"c": ["z", "z"]},
+            "d": [{"e": "f"}, {"e": "f"}, ["g", "g"]],
+        });
+        dedup_arrays(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "a": ["x", "y", 1, "1"],
+                "b": {"c": ["z"]},
+                "d": [{"e": "f"}, {"e": "f"}, ["g"]],
+            })
+        );
+    }
+
+    #[test]
+    fn test_coerce_bools() {
+        let coercions = |digits| Coercions {
+            bools: Some(digits),
+            ..Coercions::default()
+        };
+        let mut value = json!({"a": "true", "b": ["false", "1", "True"], "c": "0"});
+        coerce_leaves(&mut value, &coercions(false));
+        assert_eq!(
+            value,
+            json!({"a": true, "b": [false, "1", "True"], "c": "0"})
+        );
+        let mut value = json!({"a": "true", "b": ["false", "1", "True"], "c": "0"});
+        coerce_leaves(&mut value, &coercions(true));
+        assert_eq!(
+            value,
+            json!({"a": true, "b": [false, true, "True"], "c": false})
+        );
+    }
+
+    #[test]
+    fn test_bool_tokens() {
+        let tokens = BoolTokens {
+            truthy: vec!["on".to_owned(), "yes".to_owned()],
+            falsy: vec!["off".to_owned()],
+        };
+        let coercions = Coercions {
+            bools: Some(true),
+            bool_tokens: Some(&tokens),
+            ..Coercions::default()
+        };
+        let mut value = json!({"a": "ON", "b": ["yes", "Off", "true"], "c": "0", "d": "no"});
+        coerce_leaves(&mut value, &coercions);
+        assert_eq!(
+            value,
+            json!({"a": true, "b": [true, false, "true"], "c": false, "d": "no"})
+        );
+    }
+
+    #[test]
+    fn test_null_coercions() {
+        let coercions = Coercions {
+            empty_as_null: true,
+            null_literal: true,
+            ..Coercions::default()
+        };
+        let mut value = json!({"a": "", "b": ["null", "Null", "x"], "c": {"d": " "}});
+        coerce_leaves(&mut value, &coercions);
+        assert_eq!(
+            value,
+            json!({"a": null, "b": [null, "Null", "x"], "c": {"d": " "}})
+        );
+    }
+
+    #[test]
+    fn test_radix_literals() {
+        let mut value = json!({
+            "a": "0xFF",
+            "b": ["0o17", "0b1010"],
+            "c": "0x",
+            "d": "0x+1",
+            "e": "0x1ffffffffffffffff",
+            "f": "0b12",
+            "g": "0XFF",
+        });
+        let coercions = Coercions {
+            radix_literals: true,
+            ..Coercions::default()
+        };
+        coerce_leaves(&mut value, &coercions);
+        assert_eq!(
+            value,
+            json!({
+                "a": 255,
+                "b": [15, 10],
+                "c": "0x",
+                "d": "0x+1",
+                "e": "0x1ffffffffffffffff",
+                "f": "0b12",
+                "g": "0XFF",
+            })
+        );
+    }
+
+    #[test]
+    fn test_numbers_left_as_strings() {
+        let mut value = json!({"a": "1", "b": ["2", "x"], "c": {"d": "007", "e": "-1.5"}});
+        let expected = value.clone();
+        coerce_leaves(&mut value, &Coercions::default());
+        assert_eq!(value, expected);
+    }
+}
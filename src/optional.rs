@@ -0,0 +1,133 @@
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, web::HttpRequest, FromRequest};
+use serde::de;
+
+use crate::{parse_req, Extraction, QuerySt, QueryStConfig};
+
+/// Extract information from the request's query using `queryst`, without ever failing.
+///
+/// Both an empty query string and a query that can't be deserialized into `T` result in
+/// `OptionalQuerySt(None)`. The [**QueryStConfig**](struct.QueryStConfig.html) is still used
+/// for parsing, but its error handler is never called.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::OptionalQuerySt;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// pub struct Filter {
+///    status: String,
+/// }
+///
+/// // `/items` and `/items?bad` both get called with `None`.
+/// async fn index(OptionalQuerySt(filter): OptionalQuerySt<Filter>) -> String {
+///     match filter {
+///         Some(filter) => format!("Items with status {}", filter.status),
+///         None => "All items".to_owned(),
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/items").route(web::get().to(index)));
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct OptionalQuerySt<T>(pub Option<T>);
+
+impl_wrapper!(OptionalQuerySt<T>, Option<T>);
+
+impl<T> FromRequest for OptionalQuerySt<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Infallible>>;
+    type Config = QueryStConfig;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let query_str = req.query_string();
+        let mut extraction = Extraction::start(req, "OptionalQuerySt", query_str.len());
+        if query_str.is_empty() {
+            extraction.finish(req, &Ok(()));
+            return ready(Ok(OptionalQuerySt(None)));
+        }
+
+        let config = QueryStConfig::from_req(req);
+        let r = extraction.run(|extraction| {
+            parse_req(req, config).and_then(|value| {
+                extraction.parsed(&value);
+                QuerySt::from_value_with(value, config)
+            })
+        });
+        extraction.finish(req, &r);
+        ready(Ok(OptionalQuerySt(r.ok().map(QuerySt::into_inner))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{QueryStDiagnostics, QueryStErrorKind};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Id {
+        id: String,
+    }
+
+    #[actix_rt::test]
+    async fn test_optional_request_extract() {
+        for uri in &["/name/user1/", "/name/user1/?", "/name/user1/?name=test"] {
+            let req = TestRequest::with_uri(uri).to_srv_request();
+            let (req, mut pl) = req.into_parts();
+            let s = OptionalQuerySt::<Id>::from_request(&req, &mut pl)
+                .await
+                .unwrap();
+            assert_eq!(s.into_inner(), None, "{}", uri);
+        }
+
+        let req = TestRequest::with_uri("/name/user1/?id=test").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = OptionalQuerySt::<Id>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(
+            s.into_inner(),
+            Some(Id {
+                id: "test".to_owned()
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_diagnostics() {
+        for (uri, keys, error) in [
+            ("/?id=test&page=2", 2, None),
+            ("/?name=test", 1, Some(QueryStErrorKind::MissingField)),
+            ("/", 0, None),
+        ] {
+            let req = TestRequest::with_uri(uri).to_srv_request();
+            let (req, mut pl) = req.into_parts();
+            let _ = OptionalQuerySt::<Id>::from_request(&req, &mut pl).await;
+            assert_eq!(
+                req.extensions().get::<QueryStDiagnostics>(),
+                Some(&QueryStDiagnostics {
+                    query_len: req.query_string().len(),
+                    keys,
+                    error,
+                }),
+                "{}",
+                uri
+            );
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    normalize_with, parse_value_with, recording_query, QueryStConfig, QueryStPayloadError,
+    DEFAULT_CONFIG,
+};
+
+/// A parsed and normalized query, which deserialized types can borrow from.
+///
+/// See [`QuerySt::from_query_borrowed`](struct.QuerySt.html#method.from_query_borrowed).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedQuery(serde_json::Value);
+
+impl ParsedQuery {
+    /// Parse and normalize a query string with the default configuration
+    pub fn parse(query_str: &str) -> Result<Self, QueryStPayloadError> {
+        Self::parse_with(query_str, &DEFAULT_CONFIG)
+    }
+
+    /// Parse and normalize a query string, applying the limits and coercions of `config`
+    pub fn parse_with(
+        query_str: &str,
+        config: &QueryStConfig,
+    ) -> Result<Self, QueryStPayloadError> {
+        recording_query(query_str, || {
+            let mut value = parse_value_with(query_str, config)?;
+            normalize_with(&mut value, config)?;
+            Ok(ParsedQuery(value))
+        })
+    }
+
+    /// The normalized value, as it is handed to the deserializer
+    ///
+    /// Values that look like numbers are still strings, they are coerced by the fields that
+    /// expect a number.
+    pub fn value(&self) -> &serde_json::Value {
+        &self.0
+    }
+
+    /// Deconstruct to the normalized value
+    pub fn into_value(self) -> serde_json::Value {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::QuerySt;
+
+    #[derive(Deserialize, Debug)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        id: u64,
+        tags: Vec<&'a str>,
+    }
+
+    #[test]
+    fn test_borrowed_extract() {
+        let parsed = ParsedQuery::parse("name=a%20b&id=5&tags[]=x&tags[]=y").unwrap();
+        assert_eq!(
+            parsed.value(),
+            &json!({"name": "a b", "id": "5", "tags": ["x", "y"]})
+        );
+
+        let s = QuerySt::<Borrowed>::from_query_borrowed(&parsed).unwrap();
+        assert_eq!(s.name, "a b");
+        assert_eq!(s.id, 5);
+        assert_eq!(s.tags, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_borrowed_cow() {
+        #[derive(Deserialize, Debug)]
+        struct Labels<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            other: Cow<'a, str>,
+        }
+
+        let parsed = ParsedQuery::parse("name=a%20b&other=c").unwrap();
+        let s = QuerySt::<Labels>::from_query_borrowed(&parsed).unwrap();
+        assert!(matches!(s.name, Cow::Borrowed("a b")));
+        assert!(matches!(s.other, Cow::Owned(_)));
+        assert_eq!(s.other, "c");
+    }
+
+    #[test]
+    fn test_parse_with_config() {
+        let config = QueryStConfig::default().max_length(4);
+        assert!(matches!(
+            ParsedQuery::parse_with("name=test", &config),
+            Err(QueryStPayloadError::Overflow { .. })
+        ));
+    }
+}
@@ -0,0 +1,600 @@
+//! Checks on the query string before it is handed to `queryst::parse`.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use queryst::{ParseError, ParseErrorKind};
+
+use crate::DuplicatePolicy;
+
+/// Position of the first `%` not followed by two hex digits
+pub(crate) fn invalid_escape(query_str: &str) -> Option<usize> {
+    let bytes = query_str.as_bytes();
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| b == b'%')
+        .map(|(i, _)| i)
+        .find(|&i| {
+            !matches!(bytes.get(i + 1..i + 3), Some([a, b]) if a.is_ascii_hexdigit() && b.is_ascii_hexdigit())
+        })
+}
+
+/// Position of the escape starting the first percent-encoded sequence that isn't valid
+/// UTF-8 once decoded (ex: `%FF`), which `queryst` would replace with `U+FFFD`
+pub(crate) fn invalid_utf8(query_str: &str) -> Option<usize> {
+    if !query_str.contains('%') {
+        return None;
+    }
+    let bytes = query_str.as_bytes();
+    // The decoded bytes along with the position in the query they were decoded from
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut positions = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        positions.push(i);
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    std::str::from_utf8(&decoded)
+        .err()
+        .map(|e| positions[e.valid_up_to()])
+}
+
+/// Check every `&`-separated segment of the query on its own
+pub(crate) fn segment_errors(query_str: &str) -> Vec<ParseError> {
+    query_str
+        .split('&')
+        .filter(|segment| !segment.is_empty())
+        .filter_map(|segment| {
+            segment_error(segment).map(|reason| ParseError {
+                kind: ParseErrorKind::Other,
+                message: format!("`{}`: {}", segment, reason),
+            })
+        })
+        .collect()
+}
+
+fn segment_error(segment: &str) -> Option<String> {
+    if let Some(position) = invalid_escape(segment) {
+        return Some(format!("malformed percent escape at byte {}", position));
+    }
+    let key = match segment.find("]=") {
+        Some(pos) => &segment[..=pos],
+        None => segment.split('=').next().unwrap_or_default(),
+    };
+    if key.is_empty() {
+        return Some("empty key".to_owned());
+    }
+    let mut open = false;
+    for c in key.chars() {
+        match (c, open) {
+            ('[', false) => open = true,
+            (']', true) => open = false,
+            ('[', true) | (']', false) => return Some("unbalanced brackets in key".to_owned()),
+            _ => {}
+        }
+    }
+    if open {
+        return Some("unbalanced brackets in key".to_owned());
+    }
+    queryst::parse(segment).err().map(|e| e.message)
+}
+
+/// Deepest key chain accepted before handing a query to `queryst`, which builds and merges
+/// the parsed value recursively
+const MAX_KEY_DEPTH: usize = 128;
+
+/// A segment of a key chain as `queryst` parses it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Segment {
+    Key(String),
+    /// A numeric segment, or `[]` for keys without a value
+    Index,
+}
+
+/// Keys without a value seen so far, as a tree of their segments
+#[derive(Default)]
+struct BareKeys {
+    bare: bool,
+    children: HashMap<Segment, BareKeys>,
+}
+
+/// How the lists of a query were filled, as found by `check_keys`
+///
+/// Lists are identified by their path of segments, `Segment::Index` standing for any position
+/// in the lists along the way.
+#[derive(Debug, Default)]
+pub(crate) struct ArraySyntax {
+    /// Lists given objects with `[]`, ex: `users[][name]=a`
+    pushed: HashSet<Vec<Segment>>,
+    /// Lists given objects at an explicit index, ex: `users[0][name]=a`
+    indexed: HashSet<Vec<Segment>>,
+    /// Number of empty values pushed with `[]` to each list, ex: `items[]=`
+    pub(crate) empty_pushes: HashMap<Vec<Segment>, usize>,
+}
+
+impl ArraySyntax {
+    /// Whether any list was given objects with `[]`
+    pub(crate) fn has_pushes(&self) -> bool {
+        !self.pushed.is_empty()
+    }
+
+    /// Whether the objects of the lists at `path` were all pushed with `[]`
+    pub(crate) fn only_pushed(&self, path: &[Segment]) -> bool {
+        self.pushed.contains(path) && !self.indexed.contains(path)
+    }
+
+    fn record(&mut self, chain: &[Option<Segment>], empty_value: bool) {
+        let mut path = Vec::new();
+        for (i, segment) in chain.iter().enumerate() {
+            if i + 1 < chain.len() {
+                match segment {
+                    None => self.pushed.insert(path.clone()),
+                    Some(Segment::Index) => self.indexed.insert(path.clone()),
+                    Some(Segment::Key(_)) => false,
+                };
+            } else if segment.is_none() && empty_value {
+                *self.empty_pushes.entry(path.clone()).or_default() += 1;
+            }
+            path.push(segment.clone().unwrap_or(Segment::Index));
+        }
+    }
+}
+
+/// Reject the queries `queryst::parse` can't handle without panicking or exhausting the stack,
+/// returning how their lists were given objects otherwise
+///
+/// `queryst` panics when merging a value into the `null` of an earlier key without a value,
+/// ex: `a&a=1` or `a[b]&a[b][c]=1`. Such pairs are found by following the key chain of every
+/// pair through the bare keys before it. Array positions are not tracked, so any index is
+/// assumed to reach the `null`. Keys nested more than `MAX_KEY_DEPTH` levels are rejected as
+/// well.
+pub(crate) fn check_keys(query_str: &str) -> Result<ArraySyntax, ParseError> {
+    let error = |message| ParseError {
+        kind: ParseErrorKind::Other,
+        message,
+    };
+    let decoded = percent_decode_str(&query_str.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned();
+
+    let mut bare_keys = BareKeys::default();
+    let mut syntax = ArraySyntax::default();
+    for part in decoded.split('&') {
+        let (key, value) = match part
+            .find("]=")
+            .map(|pos| pos + 1)
+            .or_else(|| part.find('='))
+        {
+            Some(pos) => (&part[..pos], Some(&part[pos + 1..])),
+            None => (part, None),
+        };
+        let chain = key_chain(key);
+        if chain.len() > MAX_KEY_DEPTH {
+            return Err(error(format!(
+                "key nested deeper than {} levels",
+                MAX_KEY_DEPTH
+            )));
+        }
+        syntax.record(&chain, value == Some(""));
+
+        let mut node = &bare_keys;
+        for segment in chain.iter().map_while(Option::as_ref) {
+            match node.children.get(segment) {
+                Some(child) => node = child,
+                None => break,
+            }
+            if node.bare {
+                return Err(error(format!(
+                    "`{}` merges into a key given without a value",
+                    key
+                )));
+            }
+        }
+
+        if value.is_none() && !chain.is_empty() {
+            let mut node = &mut bare_keys;
+            for segment in chain {
+                node = node
+                    .children
+                    .entry(segment.unwrap_or(Segment::Index))
+                    .or_default();
+            }
+            node.bare = true;
+        }
+    }
+    Ok(syntax)
+}
+
+/// Split a key the way `queryst` does, `None` standing for `[]`
+///
+/// The parent is what comes before the first bracket, then every `[...]` without brackets
+/// inside is a child. Segments are decoded once more, and numeric ones become indices.
+fn key_chain(key: &str) -> Vec<Option<Segment>> {
+    let parent_len = key.find(['[', ']']).unwrap_or(key.len());
+    let mut raw = Vec::new();
+    if parent_len > 0 {
+        raw.push(&key[..parent_len]);
+    }
+    let mut rest = &key[parent_len..];
+    while let Some(start) = rest.find('[') {
+        let inner = &rest[start + 1..];
+        match inner.find(['[', ']']) {
+            Some(end) if inner.as_bytes()[end] == b']' => {
+                raw.push(&rest[start..start + end + 2]);
+                rest = &inner[end + 1..];
+            }
+            Some(end) => rest = &inner[end..],
+            None => break,
+        }
+    }
+
+    raw.into_iter()
+        .map(|segment| {
+            let segment = percent_decode_str(segment).decode_utf8_lossy();
+            if segment == "[]" {
+                return None;
+            }
+            let segment = match segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                Some(inner) => inner,
+                None => &segment,
+            };
+            Some(match segment.parse::<u64>() {
+                Ok(_) => Segment::Index,
+                Err(_) => Segment::Key(segment.to_owned()),
+            })
+        })
+        .collect()
+}
+
+/// Apply `policy` to keys given more than once, returning the query to parse or the
+/// repeated key
+///
+/// Keys are compared once decoded. Keys ending with `[]` are meant to be repeated and are
+/// always kept.
+pub(crate) fn dedup_keys(query_str: &str, policy: DuplicatePolicy) -> Result<Cow<'_, str>, String> {
+    let pairs = query_str
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            (
+                pair,
+                percent_decode_str(&key.replace('+', " "))
+                    .decode_utf8_lossy()
+                    .into_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut last = HashMap::new();
+    let mut duplicated = false;
+    for (i, (_, key)) in pairs.iter().enumerate() {
+        if key.ends_with("[]") {
+            continue;
+        }
+        if last.insert(key.as_str(), i).is_some() {
+            if policy == DuplicatePolicy::Error {
+                return Err(key.clone());
+            }
+            duplicated = true;
+        }
+    }
+    if !duplicated {
+        return Ok(Cow::Borrowed(query_str));
+    }
+
+    let mut seen = HashSet::new();
+    let kept = pairs
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, key))| {
+            key.ends_with("[]")
+                || match policy {
+                    DuplicatePolicy::FirstWins => seen.insert(key.as_str()),
+                    _ => last.get(key.as_str()) == Some(i),
+                }
+        })
+        .map(|(_, (pair, _))| *pair)
+        .collect::<Vec<_>>();
+    Ok(Cow::Owned(kept.join("&")))
+}
+
+/// What `lowercase_keys` escapes in the keys it rewrites, so they decode back to the same key
+const KEY: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'+')
+    .add(b';')
+    .add(b'=');
+
+/// Lowercase the keys of the query, keeping only the pairs of the spelling given last when
+/// several spellings of a key are lowercased into the same one
+///
+/// Keys are compared once decoded, so `ID=1&id=2&Id=3` gives `id=3` while `id=1&id=2` is
+/// still a repeated key. Keys ending with `[]` are meant to be repeated and are always kept.
+pub(crate) fn lowercase_keys(query_str: &str) -> Cow<'_, str> {
+    let pairs = query_str
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            let decoded = percent_decode_str(&key.replace('+', " "))
+                .decode_utf8_lossy()
+                .into_owned();
+            (pair, key, decoded)
+        })
+        .collect::<Vec<_>>();
+    if !pairs
+        .iter()
+        .any(|(_, _, key)| key.chars().any(char::is_uppercase))
+    {
+        return Cow::Borrowed(query_str);
+    }
+
+    let mut last = HashMap::new();
+    for (_, _, key) in &pairs {
+        last.insert(key.to_lowercase(), key.as_str());
+    }
+    let kept = pairs
+        .iter()
+        .filter_map(|(pair, key, decoded)| {
+            let lower = decoded.to_lowercase();
+            if !lower.ends_with("[]") && last.get(&lower) != Some(&decoded.as_str()) {
+                return None;
+            }
+            Some(if lower == *decoded {
+                (*pair).to_owned()
+            } else {
+                format!("{}{}", utf8_percent_encode(&lower, KEY), &pair[key.len()..])
+            })
+        })
+        .collect::<Vec<_>>();
+    Cow::Owned(kept.join("&"))
+}
+
+/// Rewrite the dotted keys of the query into brackets, ex: `user.name=x` into `user[name]=x`
+///
+/// Only the key before the first `=` of each pair is rewritten, and only dots outside of
+/// brackets (encoded ones included) that separate two non-empty segments, so `a.=1` and
+/// `a[b.c]=1` are kept as they are. An encoded `%2E` is never a separator.
+pub(crate) fn dots_to_brackets(query_str: &str) -> Cow<'_, str> {
+    let has_dotted_key = query_str
+        .split('&')
+        .any(|pair| pair.split('=').next().unwrap_or_default().contains('.'));
+    if !has_dotted_key {
+        return Cow::Borrowed(query_str);
+    }
+
+    let pairs = query_str
+        .split('&')
+        .map(|pair| match pair.find('=') {
+            Some(i) => format!("{}{}", dotted_key(&pair[..i]), &pair[i..]),
+            None => dotted_key(pair),
+        })
+        .collect::<Vec<_>>();
+    Cow::Owned(pairs.join("&"))
+}
+
+fn dotted_key(key: &str) -> String {
+    // Whether `rest` starts with an opening or closing bracket, and its length
+    let bracket = |rest: &str| match rest.as_bytes() {
+        [b'[', ..] => Some((true, 1)),
+        [b']', ..] => Some((false, 1)),
+        [b'%', b'5', b'B' | b'b', ..] => Some((true, 3)),
+        [b'%', b'5', b'D' | b'd', ..] => Some((false, 3)),
+        _ => None,
+    };
+
+    let mut rewritten = String::with_capacity(key.len() + 2);
+    let (mut in_brackets, mut in_segment) = (false, false);
+    let mut rest = key;
+    while let Some(c) = rest.chars().next() {
+        if let Some((opening, len)) = bracket(rest) {
+            if opening && in_segment && !in_brackets {
+                rewritten.push(']');
+                in_segment = false;
+            }
+            in_brackets = opening;
+            rewritten.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        let next = &rest[c.len_utf8()..];
+        let separates = c == '.'
+            && !in_brackets
+            && !rewritten.is_empty()
+            && !rewritten.ends_with('.')
+            && !next.is_empty()
+            && !next.starts_with('.')
+            && bracket(next).is_none();
+        if separates {
+            rewritten.push_str(if in_segment { "][" } else { "[" });
+            in_segment = true;
+        } else {
+            rewritten.push(c);
+        }
+        rest = next;
+    }
+    if in_segment {
+        rewritten.push(']');
+    }
+    rewritten
+}
+
+/// Re-encode a query whose percent-encoded bytes are in `encoding` as UTF-8
+///
+/// `queryst` decodes the whole query before splitting it, so decoding everything here and
+/// escaping only what would decode differently keeps the meaning of the query.
+#[cfg(feature = "encoding_rs")]
+pub(crate) fn transcode(query_str: &str, encoding: &'static encoding_rs::Encoding) -> String {
+    use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+    const ESCAPED: &AsciiSet = &CONTROLS.add(b' ').add(b'%').add(b'+');
+
+    let bytes = percent_decode_str(&query_str.replace('+', " ")).collect::<Vec<_>>();
+    let (decoded, _) = encoding.decode_without_bom_handling(&bytes);
+    utf8_percent_encode(&decoded, ESCAPED).to_string()
+}
+
+pub(crate) fn join_errors(errors: &[ParseError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_escape() {
+        assert_eq!(invalid_escape("a=%20&b=%2f"), None);
+        assert_eq!(invalid_escape("a=%ZZ"), Some(2));
+        assert_eq!(invalid_escape("a=1%"), Some(3));
+        assert_eq!(invalid_escape("a=%2"), Some(2));
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        assert_eq!(invalid_utf8("a=%C3%A9&b=é&c=%ZZ&d=%+F&e=%"), None);
+        assert_eq!(invalid_utf8("a=%FF"), Some(2));
+        assert_eq!(invalid_utf8("é=1&b=%C3x"), Some(7));
+        assert_eq!(invalid_utf8("a=%E2%82"), Some(2));
+    }
+
+    #[test]
+    fn test_dedup_keys() {
+        let dedup = |q, policy| dedup_keys(q, policy).map(Cow::into_owned);
+        let q = "a=1&b[]=2&a=3&b[]=4&c[d]=5&c%5Bd%5D=6";
+        assert_eq!(
+            dedup(q, DuplicatePolicy::LastWins),
+            Ok("b[]=2&a=3&b[]=4&c%5Bd%5D=6".to_owned())
+        );
+        assert_eq!(
+            dedup(q, DuplicatePolicy::FirstWins),
+            Ok("a=1&b[]=2&b[]=4&c[d]=5".to_owned())
+        );
+        assert_eq!(dedup(q, DuplicatePolicy::Error), Err("a".to_owned()));
+        assert!(matches!(
+            dedup_keys("a=1&b[]=2&b[]=3", DuplicatePolicy::Error),
+            Ok(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_lowercase_keys() {
+        assert!(matches!(
+            lowercase_keys("id=1&a[b]=X"),
+            Cow::Borrowed("id=1&a[b]=X")
+        ));
+        assert_eq!(lowercase_keys("ID=1&User[Name]=x"), "id=1&user[name]=x");
+        assert_eq!(lowercase_keys("id=1&ID=2"), "id=2");
+        assert_eq!(lowercase_keys("ID=2&id=1"), "id=1");
+        assert_eq!(lowercase_keys("ID=1&id=2&Id=3&id=4"), "id=2&id=4");
+        assert_eq!(lowercase_keys("id=1&id=2&A=3"), "id=1&id=2&a=3");
+        assert_eq!(lowercase_keys("T[]=a&t[]=b&X"), "t[]=a&t[]=b&x");
+        assert_eq!(
+            lowercase_keys("%C3%89t%C3%A9=1&A+B=2"),
+            "%C3%A9t%C3%A9=1&a%20b=2"
+        );
+    }
+
+    #[test]
+    fn test_dots_to_brackets() {
+        assert!(matches!(
+            dots_to_brackets("a[b]=1.5&c=x.y"),
+            Cow::Borrowed("a[b]=1.5&c=x.y")
+        ));
+        assert_eq!(
+            dots_to_brackets("user.name=x.y&user.tags[]=a&a.b.c&d=1"),
+            "user[name]=x.y&user[tags][]=a&a[b][c]&d=1"
+        );
+        assert_eq!(
+            dots_to_brackets("a[b].c=1&a.b[c.d]=2&a%5Bb%5D.c=3&a%2Eb=4"),
+            "a[b][c]=1&a[b][c.d]=2&a%5Bb%5D[c]=3&a%2Eb=4"
+        );
+        assert_eq!(
+            dots_to_brackets(".a=1&a.=2&a..b=3&a.[b]=4&é.ü=5"),
+            ".a=1&a.=2&a..b=3&a.[b]=4&é[ü]=5"
+        );
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_transcode() {
+        assert_eq!(
+            transcode("a=%E9+b&c%5B%5D=%25E9%2B", encoding_rs::WINDOWS_1252),
+            "a=%C3%A9%20b&c[]=%25E9%2B"
+        );
+    }
+
+    #[test]
+    fn test_check_keys() {
+        for query in [
+            "a=1&a=2&a",
+            "a[b]&a[c]=1",
+            "a[b]=1&a[b]",
+            "a[]&a[]=1",
+            "a[b][c]=1&a[b]",
+            "&&=&[]&a",
+        ] {
+            assert!(check_keys(query).is_ok(), "{}", query);
+            queryst::parse(query).unwrap();
+        }
+        for query in [
+            "a&a",
+            "a&a=1",
+            "a[b]&a[b][c]=1",
+            "a[]&a[0]=1",
+            "a[b]&a%5Bb%5D=1",
+            "a+b&a b=1",
+            "[a]&a=1",
+        ] {
+            let err = check_keys(query).unwrap_err();
+            assert!(err.message.contains("merges into a key"), "{}", query);
+        }
+
+        let deep = format!("a{}=1", "[b]".repeat(MAX_KEY_DEPTH));
+        assert!(check_keys(&deep).is_err());
+        assert!(check_keys(&deep[3..]).is_ok());
+    }
+
+    #[test]
+    fn test_segment_errors() {
+        assert!(segment_errors("a=1&b[c]=2&d[]=3&e&&f[g][h]=]").is_empty());
+
+        let messages: Vec<_> = segment_errors("=1&a[b=2&a]=3&a[[b]]=4&c=%G0")
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`=1`: empty key",
+                "`a[b=2`: unbalanced brackets in key",
+                "`a]=3`: unbalanced brackets in key",
+                "`a[[b]]=4`: unbalanced brackets in key",
+                "`c=%G0`: malformed percent escape at byte 2",
+            ]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Deserialization from a single pass over the pairs of a query string, building a tree of
+//! borrowed strings instead of a `serde_json::Value`.
+
+use std::borrow::Cow;
+#[cfg(not(feature = "preserve_order"))]
+use std::collections::BTreeMap as Entries;
+
+#[cfg(feature = "preserve_order")]
+use indexmap::IndexMap as Entries;
+
+use serde::de::{
+    self, value::MapAccessDeserializer, value::MapDeserializer, value::SeqDeserializer,
+    IntoDeserializer, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::flat::{decode, single_pass, PassError, ValueDeserializer};
+
+/// A parsed query, any string borrowing from the decoded query when it has no escapes left.
+enum Node<'a> {
+    Leaf(Option<Cow<'a, str>>),
+    Seq(Vec<Option<Cow<'a, str>>>),
+    Map(Entries<Cow<'a, str>, Node<'a>>),
+}
+
+/// Deserialize `T` from a query using named brackets (ex: `a[b][c]=1`), trailing `[]` and
+/// repeated keys.
+///
+/// Returns `None` if the query needs the full `queryst` parser: numeric indices, `[]`
+/// followed by other brackets, malformed brackets, or a key used both as a value and as a
+/// nested object or list. Otherwise the result is the same as parsing with `queryst` and
+/// applying the default normalization.
+pub(crate) fn from_query<T>(query_str: &str) -> Option<Result<T, PassError>>
+where
+    T: de::DeserializeOwned,
+{
+    // `queryst` decodes the whole query before splitting it into pairs
+    let decoded = if query_str.contains('+') {
+        Cow::Owned(decode(&query_str.replace('+', " ")).into_owned())
+    } else {
+        decode(query_str)
+    };
+
+    let mut root = Entries::new();
+    for part in decoded.split('&') {
+        let separator = part
+            .find("]=")
+            .map(|pos| pos + 1)
+            .or_else(|| part.find('='));
+        let (key, value) = match separator {
+            Some(pos) => (&part[..pos], Some(decode(&part[pos + 1..]))),
+            None => (part, None),
+        };
+        let (segments, push) = parse_key(key)?;
+        if segments.is_empty() {
+            continue;
+        }
+        insert(&mut root, segments, push, value)?;
+    }
+
+    let empty = root.is_empty();
+    Some(single_pass(empty, || {
+        serde_path_to_error::deserialize(Node::Map(root))
+    }))
+}
+
+/// Split a key into its decoded segments and whether it ends with `[]`.
+fn parse_key(key: &str) -> Option<(Vec<Cow<'_, str>>, bool)> {
+    let (parent, mut rest) = match key.find(['[', ']']) {
+        Some(pos) => (&key[..pos], &key[pos..]),
+        None => (key, ""),
+    };
+    if parent.is_empty() {
+        return if rest.is_empty() {
+            Some((Vec::new(), false))
+        } else {
+            None
+        };
+    }
+
+    let mut segments = vec![decode(parent)];
+    let mut push = false;
+    while !rest.is_empty() {
+        if push || !rest.starts_with('[') {
+            return None;
+        }
+        let end = rest.find(']')?;
+        let inner = &rest[1..end];
+        if inner.contains('[') {
+            return None;
+        }
+        if inner.is_empty() {
+            push = true;
+        } else {
+            segments.push(decode(inner));
+        }
+        rest = &rest[end + 1..];
+    }
+    // `queryst` turns numeric segments into indices, which have their own merge rules
+    if segments
+        .iter()
+        .any(|segment| segment.parse::<u64>().is_ok())
+    {
+        return None;
+    }
+    Some((segments, push))
+}
+
+/// Insert a value like `queryst` merges it, `None` for the cases left to `queryst`.
+fn insert<'a>(
+    root: &mut Entries<Cow<'a, str>, Node<'a>>,
+    mut segments: Vec<Cow<'a, str>>,
+    push: bool,
+    value: Option<Cow<'a, str>>,
+) -> Option<()> {
+    let key = segments.pop()?;
+    let mut map = root;
+    for segment in segments {
+        map = match map
+            .entry(segment)
+            .or_insert_with(|| Node::Map(Entries::new()))
+        {
+            Node::Map(map) => map,
+            _ => return None,
+        };
+    }
+
+    // like `normalize::drop_empty_array_items`, `key[]=` creates the list but adds nothing
+    let empty_push = push && value.as_deref() == Some("");
+    match (map.get_mut(&key), push, value) {
+        (None, true, _) if empty_push => {
+            map.insert(key, Node::Seq(Vec::new()));
+        }
+        (None, true, value) => {
+            map.insert(key, Node::Seq(vec![value]));
+        }
+        (None, false, value) => {
+            map.insert(key, Node::Leaf(value));
+        }
+        (Some(Node::Seq(_)), true, _) if empty_push => {}
+        (Some(Node::Seq(items)), true, value)
+        | (Some(Node::Seq(items)), false, value @ Some(_)) => items.push(value),
+        (Some(node @ Node::Leaf(Some(_))), false, value @ Some(_)) => {
+            if let Node::Leaf(first) = std::mem::replace(node, Node::Seq(Vec::new())) {
+                *node = Node::Seq(vec![first, value]);
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+impl<'de, 'a> IntoDeserializer<'de, serde_json::Error> for Node<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Hand the methods whose result depends on the target type to the leaf deserializer
+macro_rules! forward_to_leaf {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                match self {
+                    Node::Leaf(value) => ValueDeserializer(value).$method(visitor),
+                    node => node.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Node<'a> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Leaf(value) => ValueDeserializer(value).deserialize_any(visitor),
+            Node::Seq(items) => visitor.visit_seq(SeqDeserializer::new(
+                items.into_iter().map(ValueDeserializer),
+            )),
+            Node::Map(map) => visitor.visit_map(MapDeserializer::new(map.into_iter())),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Leaf(value) => ValueDeserializer(value).deserialize_option(visitor),
+            node => visitor.visit_some(node),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Leaf(value) => ValueDeserializer(value).deserialize_enum(name, variants, visitor),
+            Node::Map(map) => visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(
+                map.into_iter(),
+            ))),
+            Node::Seq(_) => Err(de::Error::invalid_type(de::Unexpected::Seq, &"a string")),
+        }
+    }
+
+    forward_to_leaf! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_identifier
+    }
+
+    forward_to_deserialize_any! {
+        bool bytes byte_buf unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn standard(query: &str) -> Value {
+        let mut value = crate::parse_value(query).unwrap();
+        crate::normalize_with(&mut value, &crate::DEFAULT_CONFIG).unwrap();
+        crate::coerced::deserialize(&value).unwrap()
+    }
+
+    #[test]
+    fn test_same_as_queryst() {
+        for query in &[
+            "a=1&b=x&&c",
+            "a=%2526&b=007&c=a+b",
+            "=x&a=-1e3",
+            "a[b][c]=1&a[b][d]=x&a[e]=2",
+            "a[]=1&a[]=&a[]=x&b=1&b=2&b=",
+            "a=1&a=2&a[]=3",
+            "a[]&a[]=1&b[c]",
+            "a%5Bb%5D=1&a[c%20d]=2",
+            "a[<=>]==23&b= x = y ",
+            "a[b]=1&a[b]=2&a[c][]=3&a[c]=4",
+            "",
+        ] {
+            let streaming: Value = from_query(query)
+                .unwrap_or_else(|| panic!("fallback for {}", query))
+                .unwrap();
+            assert_eq!(streaming, standard(query), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_needs_full_parser() {
+        for query in &[
+            "a[0]=1",
+            "0=a",
+            "a[][b]=1",
+            "a[b=1",
+            "[a]=1",
+            "a]b=1",
+            "a[b]c=1",
+            "a=1&a[b]=2",
+            "a[b]=1&a=2",
+            "a&a=1",
+            "a=1&a",
+            "a=1&a[]=2",
+        ] {
+            assert!(from_query::<Value>(query).is_none(), "{}", query);
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Sort {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Filter {
+        sort: Sort,
+        tags: Vec<String>,
+        range: Option<(u32, u32)>,
+        owner: Option<String>,
+    }
+
+    #[test]
+    fn test_typed() {
+        let s: Filter = from_query("sort=Desc&tags[]=a&tags[]=b&range=1&range=5")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            s,
+            Filter {
+                sort: Sort::Desc,
+                tags: vec!["a".to_owned(), "b".to_owned()],
+                range: Some((1, 5)),
+                owner: None,
+            }
+        );
+        let err = from_query::<Filter>("sort=Desc&tags=a")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.error.path().to_string(), "tags");
+
+        let s: Filter = from_query("sort=Asc&tags[]=123&tags[]=1.5&owner=42")
+            .unwrap()
+            .unwrap();
+        assert_eq!(s.tags, ["123", "1.5"]);
+        assert_eq!(s.owner.as_deref(), Some("42"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Serialization of a `serde_json::Value` back into a query string `queryst` parses the
+//! same way.
+
+use std::fmt::Write;
+
+use serde::ser::Error;
+use serde_json::Value;
+
+/// Percent-encode a key segment or a value
+///
+/// `queryst` decodes the whole query before splitting it, then decodes keys and values once
+/// more, so characters that would change how the query is split are encoded twice.
+fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'%' | b'&' | b'=' | b'+' | b'[' | b']' => write!(encoded, "%25{:02X}", b),
+            b if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) => {
+                encoded.push(b as char);
+                Ok(())
+            }
+            b => write!(encoded, "%{:02X}", b),
+        }
+        .unwrap_or_default();
+    }
+    encoded
+}
+
+/// Flatten `value` into its leaves, keyed with the bracket syntax
+///
+/// Arrays of plain values use `key[]`, arrays containing arrays or objects use explicit
+/// indices. Empty arrays and objects have no leaves and are left out. `segment` maps each
+/// object key before it goes into the flattened key.
+fn flatten(
+    value: &Value,
+    segment: impl Fn(&str) -> String,
+) -> Result<Vec<(String, &Value)>, serde_json::Error> {
+    let map = match value {
+        Value::Object(map) => map,
+        _ => {
+            return Err(serde_json::Error::custom(
+                "a query string needs a map or struct at the top level",
+            ))
+        }
+    };
+
+    let mut leaves = Vec::new();
+    let mut stack = map
+        .iter()
+        .rev()
+        .map(|(key, value)| (segment(key), value))
+        .collect::<Vec<_>>();
+    while let Some((key, value)) = stack.pop() {
+        match value {
+            Value::Object(map) => stack.extend(
+                map.iter()
+                    .rev()
+                    .map(|(k, v)| (format!("{}[{}]", key, segment(k)), v)),
+            ),
+            Value::Array(items) if items.iter().any(|i| i.is_array() || i.is_object()) => stack
+                .extend(
+                    items
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, v)| (format!("{}[{}]", key, i), v)),
+                ),
+            Value::Array(items) => {
+                stack.extend(items.iter().rev().map(|v| (format!("{}[]", key), v)))
+            }
+            leaf => leaves.push((key, leaf)),
+        }
+    }
+    Ok(leaves)
+}
+
+/// Write `value` as `key=value` pairs, using the bracket syntax for nested values
+///
+/// See [`flatten`] for how nested values are keyed, `null` is written as a key without a
+/// value.
+pub(crate) fn to_query_string(value: &Value) -> Result<String, serde_json::Error> {
+    let pairs = flatten(value, encode)?
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::Null => key,
+            Value::String(s) => format!("{}={}", key, encode(s)),
+            leaf => format!("{}={}", key, leaf),
+        })
+        .collect::<Vec<_>>();
+    Ok(pairs.join("&"))
+}
+
+/// Flatten `value` into unencoded `(key, value)` pairs sorted by key
+///
+/// Keys follow [`flatten`], `null` gives an empty value. The sort is stable so the items of
+/// a `key[]` list keep their order.
+pub(crate) fn to_sorted_pairs(value: &Value) -> Result<Vec<(String, String)>, serde_json::Error> {
+    let mut pairs = flatten(value, str::to_owned)?
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::Null => (key, String::new()),
+            Value::String(s) => (key, s.clone()),
+            leaf => (key, leaf.to_string()),
+        })
+        .collect::<Vec<_>>();
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_to_query_string() {
+        let value = json!({
+            "a": "x y&z",
+            "b": [1, 2],
+            "c": {"d": true, "e": null},
+            "f": [{"g": "h"}, {"g": "i"}],
+            "j": [],
+            "k=[%]+": "%41",
+        });
+        let query = to_query_string(&value).unwrap();
+        assert_eq!(
+            query,
+            "a=x%20y%2526z&b[]=1&b[]=2&c[d]=true&c[e]&f[0][g]=h&f[1][g]=i&k%253D%255B%2525%255D%252B=%252541"
+        );
+        assert_eq!(
+            queryst::parse(&query).unwrap(),
+            json!({
+                "a": "x y&z",
+                "b": ["1", "2"],
+                "c": {"d": "true", "e": null},
+                "f": [{"g": "h"}, {"g": "i"}],
+                "k=[%]+": "%41",
+            })
+        );
+
+        assert!(to_query_string(&json!(["a"])).is_err());
+    }
+
+    #[test]
+    fn test_to_sorted_pairs() {
+        let value = json!({
+            "z": "x y&z",
+            "b": ["2", 1],
+            "a": {"d": true, "c": null},
+            "f": [{"g": "h"}, {"g": "i"}],
+            "j": [],
+        });
+        assert_eq!(
+            to_sorted_pairs(&value).unwrap(),
+            [
+                ("a[c]", ""),
+                ("a[d]", "true"),
+                ("b[]", "2"),
+                ("b[]", "1"),
+                ("f[0][g]", "h"),
+                ("f[1][g]", "i"),
+                ("z", "x y&z"),
+            ]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+        );
+        assert!(to_sorted_pairs(&json!(1)).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, web::HttpRequest, FromRequest};
+use serde::de;
+use validator::Validate;
+
+use crate::{parse_req, Extraction, QuerySt, QueryStConfig, QueryStPayloadError};
+
+/// Extract information from the request's query using `queryst` and validate it.
+///
+/// Works like [**QuerySt**](struct.QuerySt.html), then runs `T::validate()` on the
+/// deserialized value. Validation failures are reported as
+/// `QueryStPayloadError::Validation` and go through the configured error handler.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{web, App};
+/// use actix_web_queryst::ValidatedQuerySt;
+/// use serde::Deserialize;
+/// use validator::Validate;
+///
+/// #[derive(Deserialize, Validate)]
+/// pub struct Pagination {
+///    #[validate(range(min = 1, max = 100))]
+///    per_page: u32,
+/// }
+///
+/// async fn index(query: ValidatedQuerySt<Pagination>) -> String {
+///     format!("{} items per page", query.per_page)
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///        web::resource("/items").route(web::get().to(index)));
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct ValidatedQuerySt<T>(pub T);
+
+impl_wrapper!(ValidatedQuerySt<T>, T);
+
+impl<T> ValidatedQuerySt<T>
+where
+    T: de::DeserializeOwned + Validate,
+{
+    /// Get query parameters from the path and validate them
+    pub fn from_query(query_str: &str) -> Result<Self, QueryStPayloadError> {
+        QuerySt::from_query(query_str)
+            .and_then(Self::validate)
+            .map_err(|e| e.with_query(query_str))
+    }
+
+    fn validate(QuerySt(inner): QuerySt<T>) -> Result<Self, QueryStPayloadError> {
+        inner
+            .validate()
+            .map_err(QueryStPayloadError::from)
+            .map(|_| ValidatedQuerySt(inner))
+    }
+}
+
+impl<T> FromRequest for ValidatedQuerySt<T>
+where
+    T: de::DeserializeOwned + Validate,
+{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, actix_web::Error>>;
+    type Config = QueryStConfig;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = QueryStConfig::from_req(req);
+        let mut extraction = Extraction::start(req, "ValidatedQuerySt", req.query_string().len());
+        let r = extraction.run(|extraction| {
+            parse_req(req, config)
+                .and_then(|value| {
+                    extraction.parsed(&value);
+                    QuerySt::from_value_with(value, config)
+                })
+                .and_then(Self::validate)
+        });
+        extraction.finish(req, &r);
+        ready(r.map_err(|e| config.handle_error(e, req)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Validate, Debug)]
+    struct Pagination {
+        #[validate(range(min = 1, max = 100))]
+        per_page: u32,
+    }
+
+    #[actix_rt::test]
+    async fn test_validated_request_extract() {
+        let req = TestRequest::with_uri("/?per_page=10").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let s = ValidatedQuerySt::<Pagination>::from_request(&req, &mut pl)
+            .await
+            .unwrap();
+        assert_eq!(s.per_page, 10);
+
+        let req = TestRequest::with_uri("/?per_page=500").to_srv_request();
+        let (req, mut pl) = req.into_parts();
+        let err = ValidatedQuerySt::<Pagination>::from_request(&req, &mut pl)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_validation_error_display() {
+        let err = ValidatedQuerySt::<Pagination>::from_query("per_page=0").unwrap_err();
+        assert!(matches!(err, QueryStPayloadError::Validation(_, _)));
+        assert!(err.to_string().contains("per_page"), "{}", err);
+    }
+}
@@ -0,0 +1,133 @@
+//! End-to-end tests sending requests through an actix `App`, covering the `FromRequest`
+//! wiring and the lookup of the config registered on the app or the resource.
+
+use actix_web::error::InternalError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App, HttpResponse};
+use actix_web_queryst::{QuerySt, QueryStConfig};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+enum ResponseType {
+    Token,
+    Code,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthRequest {
+    id: u64,
+    response_type: ResponseType,
+}
+
+async fn index(info: QuerySt<AuthRequest>) -> String {
+    format!(
+        "Authorization request for id={} and type={:?}!",
+        info.id, info.response_type
+    )
+}
+
+async fn get(uri: &str) -> (StatusCode, String) {
+    let (status, _, body) = get_with_type(uri).await;
+    (status, body)
+}
+
+async fn get_with_type(uri: &str) -> (StatusCode, Option<String>, String) {
+    let mut app = test::init_service(
+        App::new()
+            .app_data(QueryStConfig::default().alias("type", "response_type"))
+            .route("/auth", web::get().to(index))
+            .service(
+                web::resource("/custom")
+                    .app_data(QueryStConfig::default().error_handler(|e, _| {
+                        let body = format!("custom error for `{}`", e.query().unwrap_or_default());
+                        InternalError::from_response(e, HttpResponse::Conflict().body(body)).into()
+                    }))
+                    .route(web::get().to(index)),
+            )
+            .service(
+                web::resource("/json")
+                    .app_data(QueryStConfig::default().json_errors(true))
+                    .route(web::get().to(index)),
+            ),
+    )
+    .await;
+    let req = test::TestRequest::get().uri(uri).to_request();
+    let resp = test::call_service(&mut app, req).await;
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_owned());
+    let body = test::read_body(resp).await;
+    (
+        status,
+        content_type,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+#[actix_rt::test]
+async fn extracts_query() {
+    let (status, body) = get("/auth?id=64&response_type=Code").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "Authorization request for id=64 and type=Code!");
+
+    let (status, body) = get("/auth?type=Token&id=1&extra=x").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "Authorization request for id=1 and type=Token!");
+}
+
+#[actix_rt::test]
+async fn rejects_bad_input() {
+    let (status, body) = get("/auth").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "QuerySt query string is required");
+
+    let (status, _) = get("/auth?id=64&id[x]=1&response_type=Code").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = get("/auth?id=64&response_type=Code&flag&flag=1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn uses_resource_error_handler() {
+    let (status, body) = get("/custom?id=x&response_type=Code").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, "custom error for `id=x&response_type=Code`");
+
+    let (status, _) = get("/custom?id=64&response_type=Code").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The resource config is used as a whole, without the alias of the app config
+    let (status, body) = get("/custom?id=64&type=Code").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, "custom error for `id=64&type=Code`");
+
+    let (status, _) = get("/auth?id=x&response_type=Code").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_rt::test]
+async fn responds_with_json_errors() {
+    let (status, content_type, body) = get_with_type("/json?id=x&response_type=Code").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(content_type.as_deref(), Some("application/json"));
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["kind"], "type_mismatch");
+    assert!(
+        body["error"].as_str().unwrap().starts_with("QuerySt"),
+        "{}",
+        body
+    );
+
+    let (status, _, body) = get_with_type("/json").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body,
+        r#"{"error":"QuerySt query string is required","kind":"missing_field"}"#
+    );
+
+    let (_, content_type, _) = get_with_type("/auth?id=x&response_type=Code").await;
+    assert_eq!(content_type.as_deref(), Some("text/plain; charset=utf-8"));
+}
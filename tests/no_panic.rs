@@ -0,0 +1,68 @@
+//! Property tests feeding hostile query strings to `QuerySt`, which must return a result
+//! rather than panic. The `fuzz/` directory has a `cargo-fuzz` target doing the same with
+//! arbitrary bytes.
+
+use actix_web_queryst::{QuerySt, QueryStConfig};
+use proptest::prelude::*;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Typed {
+    a: Option<Vec<u32>>,
+    b: Option<String>,
+}
+
+/// Queries built from the pieces `queryst` gives a meaning to, so most of them get past the
+/// decoding and exercise the merge rules
+fn query() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        Just("a"),
+        Just("b"),
+        Just("0"),
+        Just("1"),
+        Just("["),
+        Just("]"),
+        Just("[]"),
+        Just("[0]"),
+        Just("="),
+        Just("]="),
+        Just("&"),
+        Just(";"),
+        Just("+"),
+        Just("%"),
+        Just("%5B"),
+        Just("%5D"),
+        Just("%26"),
+        Just("%25"),
+        Just("%FF"),
+        Just("é"),
+    ];
+    prop::collection::vec(piece, 0..16).prop_map(|pieces| pieces.concat())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn structured_queries(query in query()) {
+        let _ = QuerySt::<serde_json::Value>::from_query(&query);
+        let _ = QuerySt::<Typed>::from_query(&query);
+        let config = QueryStConfig::default()
+            .allow_semicolons(true)
+            .coerce_bools(true)
+            .bare_flags_as_true(true);
+        let _ = QuerySt::<serde_json::Value>::from_query_with(&query, &config);
+    }
+
+    #[test]
+    fn arbitrary_strings(query in any::<String>()) {
+        let _ = QuerySt::<serde_json::Value>::from_query(&query);
+    }
+}
+
+#[test]
+fn deep_keys() {
+    let query = format!("a{}=1", "[b]".repeat(100_000));
+    assert!(QuerySt::<serde_json::Value>::from_query(&query).is_err());
+}
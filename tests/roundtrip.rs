@@ -0,0 +1,134 @@
+//! Property tests serializing random values with `try_into_query_string` and extracting them
+//! back with `QuerySt`.
+//!
+//! Limitations of the generated values, which the query syntax can't round-trip:
+//! - strings are never `true`/`false` with `coerce_bools`, which turns them into booleans
+//! - map keys start with a letter, since numeric ones are taken as list indices
+//! - list elements are non-empty, since empty ones are dropped
+//! - lists and maps may be empty only on `#[serde(default)]` fields, since empty containers
+//!   are left out of the query string
+//! - floats are finite
+
+use std::collections::BTreeMap;
+
+use actix_web_queryst::{QuerySt, QueryStConfig};
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Filter {
+    status: String,
+    #[serde(default)]
+    levels: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Item {
+    label: String,
+    weight: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Record {
+    name: String,
+    count: u32,
+    offset: i64,
+    ratio: f64,
+    note: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    ids: Vec<u64>,
+    filter: Filter,
+    #[serde(default)]
+    items: Vec<Item>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Flags {
+    name: String,
+    active: bool,
+    #[serde(default)]
+    switches: Vec<bool>,
+}
+
+/// Non-empty strings, with characters that need encoding and digit-only ones
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9 _.~&=%+;#?/\\[\\]éü€-]{1,13}",
+        "-?[0-9]{1,6}(\\.[0-9]{1,3})?",
+    ]
+}
+
+/// Map keys that are never taken as list indices
+fn key() -> impl Strategy<Value = String> {
+    "[a-zA-Z][a-zA-Z0-9 _.~&=%+;#?/\\[\\]éü€-]{0,12}"
+}
+
+fn filter() -> impl Strategy<Value = Filter> {
+    (text(), prop::collection::vec(any::<u8>(), 0..4))
+        .prop_map(|(status, levels)| Filter { status, levels })
+}
+
+fn item() -> impl Strategy<Value = Item> {
+    (text(), any::<u16>()).prop_map(|(label, weight)| Item { label, weight })
+}
+
+fn record() -> impl Strategy<Value = Record> {
+    (
+        (text(), any::<u32>(), any::<i64>(), -1e9..1e9f64),
+        (
+            prop::option::of(text()),
+            prop::collection::vec(text(), 0..4),
+            prop::collection::vec(any::<u64>(), 0..4),
+        ),
+        (
+            filter(),
+            prop::collection::vec(item(), 0..12),
+            prop::collection::btree_map(key(), text(), 0..4),
+        ),
+    )
+        .prop_map(
+            |((name, count, offset, ratio), (note, tags, ids), (filter, items, labels))| Record {
+                name,
+                count,
+                offset,
+                ratio,
+                note,
+                tags,
+                ids,
+                filter,
+                items,
+                labels,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn test_record_roundtrip(record in record()) {
+        let query = QuerySt(record.clone()).try_into_query_string().unwrap();
+        let parsed = QuerySt::<Record>::from_query(&query);
+        prop_assert!(parsed.is_ok(), "{}: {}", query, parsed.unwrap_err());
+        prop_assert_eq!(&parsed.unwrap().into_inner(), &record, "{}", query);
+
+        let streaming = QuerySt::<Record>::from_query_streaming(&query).unwrap();
+        prop_assert_eq!(streaming.into_inner(), record, "{}", query);
+    }
+
+    #[test]
+    fn test_flags_roundtrip(
+        name in text().prop_filter("coerced into a boolean", |s| s != "true" && s != "false"),
+        active in any::<bool>(),
+        switches in prop::collection::vec(any::<bool>(), 0..4),
+    ) {
+        let flags = Flags { name, active, switches };
+        let query = QuerySt(flags.clone()).try_into_query_string().unwrap();
+        let config = QueryStConfig::default().coerce_bools(true);
+        let parsed = QuerySt::<Flags>::from_query_with(&query, &config);
+        prop_assert!(parsed.is_ok(), "{}: {}", query, parsed.unwrap_err());
+        prop_assert_eq!(parsed.unwrap().into_inner(), flags, "{}", query);
+    }
+}